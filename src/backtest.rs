@@ -0,0 +1,34 @@
+use crate::{error::Result, repository::Repository};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Action {
+    Pass,
+    Invest(f64),
+    Redeem(f64),
+}
+
+/// Decides what to do on the day returned by `Repository::check`.
+pub trait Strategy {
+    fn decide(&mut self, repository: &Repository) -> Action;
+}
+
+pub fn run(repository: &mut Repository, strategy: &mut dyn Strategy) -> Result<()> {
+    run_observed(repository, strategy, &mut |_| Ok(()))
+}
+
+/// Like `run`, calling `observer` after each day.
+pub fn run_observed(
+    repository: &mut Repository,
+    strategy: &mut dyn Strategy,
+    observer: &mut dyn FnMut(&Repository) -> Result<()>,
+) -> Result<()> {
+    while repository.check().is_ok() {
+        match strategy.decide(repository) {
+            Action::Pass => repository.pass(),
+            Action::Invest(investment) => repository.invest(investment),
+            Action::Redeem(redemption) => repository.redeem(redemption),
+        }?;
+        observer(repository)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,198 @@
+use crate::{
+    config::Config,
+    data,
+    error::{Error, Result},
+    metrics::{Metric, Metrics},
+};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+/// Runs the strategy on every `.csv` history in a directory, e.g.
+///
+/// ```toml
+/// [batch]
+/// directory = "funds"
+/// rank_by = ["xirr", "max_drawdown"]
+/// ```
+///
+/// Funds are ordered by the first metric in `rank_by` and ranked by each.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchConfig {
+    pub directory: PathBuf,
+    pub rank_by: Vec<Metric>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Fund {
+    pub name: String,
+    pub path: PathBuf,
+    pub metrics: Option<Metrics>,
+    /// The rank by each metric of `rank_by`, 1 being the best.
+    pub ranks: Vec<usize>,
+    /// What failed on which file and why, e.g. `loading funds/b.csv: Invalid`.
+    pub error: Option<String>,
+}
+
+impl Fund {
+    fn new(config: &Config, path: PathBuf) -> Self {
+        let name = path
+            .file_stem()
+            .map_or_else(String::new, |stem| stem.to_string_lossy().into_owned());
+        let (metrics, error) = match Self::run(config, &path) {
+            Ok(metrics) => (Some(metrics), None),
+            Err(err) => (None, Some(err)),
+        };
+        Self {
+            name,
+            path,
+            metrics,
+            ranks: vec![],
+            error,
+        }
+    }
+
+    fn run(config: &Config, path: &Path) -> std::result::Result<Metrics, String> {
+        let history =
+            data::load(path).map_err(|err| format!("loading {}: {}", path.display(), err))?;
+        let repository = config
+            .run(history)
+            .map_err(|err| format!("running on {}: {}", path.display(), err))?;
+        Ok(Metrics::new(&repository))
+    }
+
+    fn get(&self, metric: Metric) -> f64 {
+        self.metrics
+            .as_ref()
+            .map_or(f64::NAN, |metrics| metrics.get(metric))
+    }
+}
+
+/// Funds that failed are kept last with their errors.
+pub fn batch(config: &Config, batch: &BatchConfig) -> Result<Vec<Fund>> {
+    if batch.rank_by.is_empty() {
+        return Err(Error::Invalid);
+    }
+    let mut paths = vec![];
+    for entry in fs::read_dir(&batch.directory)? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|extension| extension == "csv") {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+    let mut funds: Vec<Fund> = paths
+        .into_par_iter()
+        .map(|path| Fund::new(config, path))
+        .collect();
+    for (i, &metric) in batch.rank_by.iter().enumerate().rev() {
+        funds.sort_by(|a, b| metric.compare(a.get(metric), b.get(metric)));
+        for (rank, fund) in funds.iter_mut().enumerate() {
+            fund.ranks.resize(batch.rank_by.len(), 0);
+            fund.ranks[i] = if fund.metrics.is_some() { rank + 1 } else { 0 };
+        }
+    }
+    Ok(funds)
+}
+
+/// Writes the funds as a tab-separated table.
+pub fn write_table<W: Write>(mut writer: W, funds: &[Fund]) -> std::io::Result<()> {
+    writeln!(
+        writer,
+        "fund\tranks\tinvestment\tprofit\ttotal_return\txirr\tmax_drawdown"
+    )?;
+    for fund in funds {
+        let ranks: Vec<String> = fund.ranks.iter().map(usize::to_string).collect();
+        match (&fund.metrics, &fund.error) {
+            (Some(metrics), _) => writeln!(
+                writer,
+                "{}\t{}\t{:.2}\t{:.2}\t{:.4}\t{:.4}\t{:.4}",
+                fund.name,
+                ranks.join(","),
+                metrics.investment,
+                metrics.profit,
+                metrics.total_return,
+                metrics.xirr,
+                metrics.max_drawdown
+            )?,
+            (None, error) => writeln!(
+                writer,
+                "{}\t-\terror: {}",
+                fund.name,
+                error.as_deref().unwrap_or_default()
+            )?,
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_batch() {
+        let directory = std::env::temp_dir().join(format!("fund-batch-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        fs::write(
+            directory.join("a.csv"),
+            "date,nav\n2021-01-01,1.0\n2021-01-02,1.1\n2021-01-03,1.2\n",
+        )
+        .unwrap();
+        fs::write(
+            directory.join("b.csv"),
+            "date,nav\n2021-01-01,1.0\n2021-01-02,-\n",
+        )
+        .unwrap();
+        fs::write(directory.join("notes.txt"), "not a fund").unwrap();
+        let config: Config = toml::from_str(
+            r#"
+            [data]
+            path = "a.csv"
+
+            [strategy]
+            type = "dca"
+            amount = 100.0
+            interval = 1
+            "#,
+        )
+        .unwrap();
+        let funds = batch(
+            &config,
+            &BatchConfig {
+                directory: directory.clone(),
+                rank_by: vec![Metric::Profit, Metric::MaxDrawdown],
+            },
+        )
+        .unwrap();
+        assert_eq!(funds.len(), 2);
+        assert_eq!(funds[0].name, "a");
+        assert_eq!(funds[0].ranks, vec![1, 1]);
+        assert!(funds[0].error.is_none());
+        assert!(funds[0].metrics.as_ref().unwrap().profit > 0.);
+        assert_eq!(funds[1].name, "b");
+        assert_eq!(funds[1].ranks, vec![0, 0]);
+        assert!(funds[1].metrics.is_none());
+        let error = format!("loading {}: Invalid", directory.join("b.csv").display());
+        assert_eq!(funds[1].path, directory.join("b.csv"));
+        assert_eq!(funds[1].error.as_ref(), Some(&error));
+
+        let mut table = vec![];
+        write_table(&mut table, &funds).unwrap();
+        let table = String::from_utf8(table).unwrap();
+        assert!(table
+            .lines()
+            .nth(1)
+            .unwrap()
+            .starts_with("a\t1,1\t300.00\t"));
+        assert_eq!(
+            table.lines().nth(2),
+            Some(format!("b\t-\terror: {}", error).as_str())
+        );
+        fs::remove_dir_all(directory).unwrap();
+    }
+}
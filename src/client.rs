@@ -0,0 +1,347 @@
+//! The client side of the text protocol of `server`, so that a strategy
+//! written against `Simulator` runs the same on a remote server, through
+//! `Client`, or in process, through `Mock`.
+//!
+//! Opening another fund and `reset` are left to the JSON protocol, which this
+//! client does not speak: a strategy starts over with a new `Client` or `Mock`.
+
+use crate::{
+    backtest::Action,
+    error::{Error, Result},
+    repository::Repository,
+    server::{text, Session},
+};
+use chrono::NaiveDate;
+use std::{
+    io::{self, BufRead, BufReader, Read, Write},
+    net::{TcpStream, ToSocketAddrs},
+};
+
+/// A simulation driven one day at a time, as `Repository`.
+pub trait Simulator {
+    /// The day to trade on, as `Repository::check`.
+    fn next(&self) -> Result<(NaiveDate, f64)>;
+
+    fn pass(&mut self) -> Result<()>;
+
+    fn invest(&mut self, investment: f64) -> Result<()>;
+
+    fn redeem(&mut self, redemption: f64) -> Result<()>;
+
+    /// Passes up to `days` days, fewer if the last comes first.
+    fn pass_days(&mut self, days: usize) -> Result<()>;
+
+    /// Passes the days before `date`.
+    fn pass_until(&mut self, date: NaiveDate) -> Result<()>;
+
+    /// Carries out `actions` on consecutive days, all of them or none.
+    fn batch(&mut self, actions: &[Action]) -> Result<()>;
+
+    /// Passes the remaining days and ends the session.
+    fn finish(&mut self) -> Result<()>;
+}
+
+pub struct Client<S = TcpStream> {
+    stream: BufReader<S>,
+    reply: Reply,
+}
+
+impl Client {
+    pub fn connect<A: ToSocketAddrs>(address: A) -> Result<Self> {
+        Self::new(TcpStream::connect(address)?)
+    }
+}
+
+impl<S: Read + Write> Client<S> {
+    /// Starts a session over `stream`, e.g. a `UnixStream`.
+    pub fn new(stream: S) -> Result<Self> {
+        let mut client = Self {
+            stream: BufReader::new(stream),
+            reply: Reply(String::new()),
+        };
+        client.reply.0 = client.receive()?;
+        Ok(client)
+    }
+
+    fn order(&mut self, line: &str) -> Result<()> {
+        // The server closes the connection after the last day.
+        self.reply.next()?;
+        writeln!(self.stream.get_mut(), "{}", line)?;
+        let reply = self.receive()?;
+        self.reply.update(reply)
+    }
+
+    fn receive(&mut self) -> Result<String> {
+        let mut line = String::new();
+        if self.stream.read_line(&mut line)? == 0 {
+            return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+        }
+        Ok(String::from(line.trim_end()))
+    }
+}
+
+impl<S: Read + Write> Simulator for Client<S> {
+    fn next(&self) -> Result<(NaiveDate, f64)> {
+        self.reply.next()
+    }
+
+    fn pass(&mut self) -> Result<()> {
+        self.order("p")
+    }
+
+    fn invest(&mut self, investment: f64) -> Result<()> {
+        self.order(&format!("i{}", investment))
+    }
+
+    fn redeem(&mut self, redemption: f64) -> Result<()> {
+        self.order(&format!("r{}", redemption))
+    }
+
+    fn pass_days(&mut self, days: usize) -> Result<()> {
+        self.order(&format!("p{}", days))
+    }
+
+    fn pass_until(&mut self, date: NaiveDate) -> Result<()> {
+        self.order(&format!("u{}", date))
+    }
+
+    fn batch(&mut self, actions: &[Action]) -> Result<()> {
+        self.order(&batch(actions))
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        if self.reply.next().is_ok() {
+            writeln!(self.stream.get_mut(), "e")?;
+        }
+        self.reply = Reply::ended();
+        Ok(())
+    }
+}
+
+/// A server in process, answering as `Client` would over the network.
+pub struct Mock {
+    session: Session,
+    reply: Reply,
+}
+
+impl Mock {
+    pub fn new(repository: Repository) -> Self {
+        let session = Session::new(repository);
+        Self {
+            reply: Reply(text::greeting(&session)),
+            session,
+        }
+    }
+
+    pub fn into_repository(self) -> Option<Repository> {
+        self.session.into_repository()
+    }
+
+    fn order(&mut self, line: &str) -> Result<()> {
+        self.reply.next()?;
+        let (reply, _) = text::handle(&mut self.session, line);
+        self.reply.update(reply.ok_or(Error::Invalid)?)
+    }
+}
+
+impl Simulator for Mock {
+    fn next(&self) -> Result<(NaiveDate, f64)> {
+        self.reply.next()
+    }
+
+    fn pass(&mut self) -> Result<()> {
+        self.order("p")
+    }
+
+    fn invest(&mut self, investment: f64) -> Result<()> {
+        self.order(&format!("i{}", investment))
+    }
+
+    fn redeem(&mut self, redemption: f64) -> Result<()> {
+        self.order(&format!("r{}", redemption))
+    }
+
+    fn pass_days(&mut self, days: usize) -> Result<()> {
+        self.order(&format!("p{}", days))
+    }
+
+    fn pass_until(&mut self, date: NaiveDate) -> Result<()> {
+        self.order(&format!("u{}", date))
+    }
+
+    fn batch(&mut self, actions: &[Action]) -> Result<()> {
+        self.order(&batch(actions))
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        text::handle(&mut self.session, "e");
+        self.reply = Reply::ended();
+        Ok(())
+    }
+}
+
+/// `actions` as orders separated by `;`, e.g. `i1000;p;r500`.
+fn batch(actions: &[Action]) -> String {
+    actions
+        .iter()
+        .map(|action| match action {
+            Action::Pass => String::from("p"),
+            Action::Invest(investment) => format!("i{}", investment),
+            Action::Redeem(redemption) => format!("r{}", redemption),
+        })
+        .collect::<Vec<_>>()
+        .join(";")
+}
+
+/// The last `+<date> <net asset value>` or `-<error>` of the server.
+struct Reply(String);
+
+impl Reply {
+    fn ended() -> Self {
+        Reply(format!("-{}", Error::Overflow))
+    }
+
+    fn next(&self) -> Result<(NaiveDate, f64)> {
+        let mut reply = self.0.splitn(2, ' ');
+        match (reply.next(), reply.next()) {
+            (Some(date), Some(nav)) if date.starts_with('+') => Ok((
+                date[1..].parse().map_err(|_| Error::Invalid)?,
+                nav.parse().map_err(|_| Error::Invalid)?,
+            )),
+            _ => Err(match self.0.as_str() {
+                "-Insufficient" => Error::Insufficient,
+                "-Overflow" => Error::Overflow,
+                _ => Error::Invalid,
+            }),
+        }
+    }
+
+    /// Takes `reply` to an order, which failed unless it tells the next day,
+    /// or that there is none after the last.
+    fn update(&mut self, reply: String) -> Result<()> {
+        let last = self.next().is_ok();
+        let reply = Reply(reply);
+        match reply.next() {
+            Ok(_) => {}
+            Err(Error::Overflow) if last => {}
+            Err(err) => return Err(err),
+        }
+        *self = reply;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::Server;
+    use std::{net::TcpListener, thread};
+
+    fn history() -> Vec<(NaiveDate, f64)> {
+        NaiveDate::from_ymd(2021, 1, 1)
+            .iter_days()
+            .take(3)
+            .map(|date| (date, 1.0))
+            .collect()
+    }
+
+    fn strategy(simulator: &mut dyn Simulator) {
+        assert_eq!(
+            simulator.next().unwrap(),
+            (NaiveDate::from_ymd(2021, 1, 1), 1.0)
+        );
+        assert!(if let Err(Error::Insufficient) = simulator.redeem(1.0) {
+            true
+        } else {
+            false
+        });
+        assert!(simulator.invest(100.0).is_ok());
+        assert!(simulator.pass().is_ok());
+        assert_eq!(
+            simulator.next().unwrap(),
+            (NaiveDate::from_ymd(2021, 1, 3), 1.0)
+        );
+        assert!(simulator.redeem(50.0).is_ok());
+        assert!(if let Err(Error::Overflow) = simulator.next() {
+            true
+        } else {
+            false
+        });
+        assert!(if let Err(Error::Overflow) = simulator.pass() {
+            true
+        } else {
+            false
+        });
+        assert!(simulator.finish().is_ok());
+    }
+
+    #[test]
+    fn test_client() {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let address = listener.local_addr().unwrap();
+        let server =
+            thread::spawn(move || Server::run(Box::new(|_| 0.0), history(), listener).unwrap());
+        strategy(&mut Client::connect(address).unwrap());
+        let remote = server.join().unwrap();
+
+        let mut mock = Mock::new(Repository::new(Box::new(|_| 0.0), history()).unwrap());
+        strategy(&mut mock);
+        let local = mock.into_repository().unwrap();
+        assert_eq!(remote.transactions(), local.transactions());
+        assert_eq!(remote.daily_infos(), local.daily_infos());
+    }
+
+    #[test]
+    fn test_fast_forward() {
+        let history: Vec<(NaiveDate, f64)> = NaiveDate::from_ymd(2021, 1, 1)
+            .iter_days()
+            .take(10)
+            .map(|date| (date, 1.0))
+            .collect();
+        let strategy = |simulator: &mut dyn Simulator| {
+            assert!(simulator.pass_days(2).is_ok());
+            assert!(simulator
+                .pass_until(NaiveDate::from_ymd(2021, 1, 5))
+                .is_ok());
+            assert_eq!(
+                simulator.next().unwrap(),
+                (NaiveDate::from_ymd(2021, 1, 5), 1.0)
+            );
+            assert!(simulator
+                .batch(&[Action::Invest(100.0), Action::Pass, Action::Redeem(50.0)])
+                .is_ok());
+            assert!(if let Err(Error::Insufficient) =
+                simulator.batch(&[Action::Pass, Action::Redeem(100.0)])
+            {
+                true
+            } else {
+                false
+            });
+            assert_eq!(
+                simulator.next().unwrap(),
+                (NaiveDate::from_ymd(2021, 1, 8), 1.0)
+            );
+            assert!(simulator.pass_days(5).is_ok());
+            assert!(if let Err(Error::Overflow) = simulator.next() {
+                true
+            } else {
+                false
+            });
+            assert!(simulator.finish().is_ok());
+        };
+        let rule = || Box::new(|_| 0.0);
+
+        let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let address = listener.local_addr().unwrap();
+        let remote = history.clone();
+        let server = thread::spawn(move || Server::run(rule(), remote, listener).unwrap());
+        strategy(&mut Client::connect(address).unwrap());
+        let remote = server.join().unwrap();
+
+        let mut mock = Mock::new(Repository::new(rule(), history).unwrap());
+        strategy(&mut mock);
+        let local = mock.into_repository().unwrap();
+        assert_eq!(remote.transactions(), local.transactions());
+        assert_eq!(remote.daily_infos(), local.daily_infos());
+    }
+}
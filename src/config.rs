@@ -0,0 +1,299 @@
+use crate::{
+    backtest::{self, Strategy},
+    batch::BatchConfig,
+    data,
+    error::{Error, Result},
+    repository::{Repository, Rule},
+    rolling::RollingConfig,
+    rules::{CheapestFirst, Fifo, Lifo, Recurring},
+    schedule::Schedule,
+    share_class::ShareClassConfig,
+    strategies::{Dca, MovingAverage},
+    stream::StreamConfig,
+    sweep::SweepConfig,
+    synthetic::MonteCarloConfig,
+    walk_forward::WalkForwardConfig,
+};
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// A complete experiment, loaded from a TOML file such as
+///
+/// ```toml
+/// [data]
+/// path = "110022.csv"
+/// start = "2019-01-01"
+/// end = "2020-12-31"
+/// warm_up = 60
+///
+/// [rule]
+/// investment_rates = [[1000000.0, 0.015], [5000000.0, 0.012]]
+/// redemption_rates = [[7, 0.015], [365, 0.005]]
+/// lots = "cheapest_first" # "fifo" by default, or "lifo"
+/// # or `schedule = "110022.toml"`, see `schedule::Schedule`
+///
+/// [cash]
+/// initial = 100000.0
+///
+/// [strategy]
+/// type = "dca"
+/// amount = 1000.0
+/// interval = 20
+///
+/// [benchmark]
+/// path = "000300.csv"
+///
+/// [output]
+/// path = "result.json"
+/// ```
+///
+/// plus the optional sections of the other modes, e.g. `sweep::SweepConfig`.
+/// Relative paths are resolved against the directory of the file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Config {
+    pub data: DataConfig,
+    #[serde(default)]
+    pub rule: RuleConfig,
+    #[serde(default)]
+    pub cash: CashConfig,
+    pub strategy: StrategyConfig,
+    pub benchmark: Option<BenchmarkConfig>,
+    #[serde(default)]
+    pub output: OutputConfig,
+    pub sweep: Option<SweepConfig>,
+    pub walk_forward: Option<WalkForwardConfig>,
+    pub rolling: Option<RollingConfig>,
+    pub monte_carlo: Option<MonteCarloConfig>,
+    pub batch: Option<BatchConfig>,
+    pub share_classes: Option<ShareClassConfig>,
+    pub stream: Option<StreamConfig>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DataConfig {
+    /// Only optional for modes that bring their own data, e.g. `batch`.
+    pub path: Option<PathBuf>,
+    pub start: Option<NaiveDate>,
+    pub end: Option<NaiveDate>,
+    /// Records before `start` visible to the strategy, defaulting to its
+    /// lookback.
+    pub warm_up: Option<usize>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RuleConfig {
+    #[serde(default)]
+    pub investment_rates: Vec<(f64, f64)>,
+    #[serde(default)]
+    pub redemption_rates: Vec<(usize, f64)>,
+    #[serde(default)]
+    pub lots: LotOrder,
+    /// Annual rates accrued daily, only for series that do not include them.
+    #[serde(default)]
+    pub management: f64,
+    #[serde(default)]
+    pub custody: f64,
+    #[serde(default)]
+    pub sales_service: f64,
+    /// Replaces the fields above, either inline or as the path of a file.
+    pub schedule: Option<ScheduleSource>,
+}
+
+/// Which lots redemptions consume first.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LotOrder {
+    #[default]
+    Fifo,
+    Lifo,
+    CheapestFirst,
+}
+
+impl LotOrder {
+    pub fn build(
+        self,
+        investment_rates: Vec<(f64, f64)>,
+        redemption_rates: Vec<(usize, f64)>,
+    ) -> Box<dyn Rule> {
+        match self {
+            LotOrder::Fifo => Box::new(Fifo::new(investment_rates, redemption_rates)),
+            LotOrder::Lifo => Box::new(Lifo::new(investment_rates, redemption_rates)),
+            LotOrder::CheapestFirst => {
+                Box::new(CheapestFirst::new(investment_rates, redemption_rates))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ScheduleSource {
+    Path(PathBuf),
+    Inline(Schedule),
+}
+
+impl RuleConfig {
+    pub fn build(&self) -> Result<Box<dyn Rule>> {
+        match &self.schedule {
+            Some(_) if self.is_flat() => return Err(Error::Invalid),
+            Some(ScheduleSource::Path(path)) => return Schedule::load(path)?.build(),
+            Some(ScheduleSource::Inline(schedule)) => return schedule.build(),
+            None => {}
+        }
+        let rule = self
+            .lots
+            .build(self.investment_rates.clone(), self.redemption_rates.clone());
+        Ok(
+            if self.management == 0. && self.custody == 0. && self.sales_service == 0. {
+                rule
+            } else {
+                Box::new(
+                    Recurring::new(rule)
+                        .with_management(self.management)
+                        .with_custody(self.custody)
+                        .with_sales_service(self.sales_service),
+                )
+            },
+        )
+    }
+
+    /// Whether any of the fields replaced by a schedule is set.
+    fn is_flat(&self) -> bool {
+        !self.investment_rates.is_empty()
+            || !self.redemption_rates.is_empty()
+            || self.lots != LotOrder::Fifo
+            || self.management != 0.
+            || self.custody != 0.
+            || self.sales_service != 0.
+    }
+
+    /// Loads the schedule file, if any, relative to `base`.
+    pub(crate) fn resolve(&mut self, base: &Path) -> Result<()> {
+        if let Some(ScheduleSource::Path(path)) = &self.schedule {
+            self.schedule = Some(ScheduleSource::Inline(Schedule::load(base.join(path))?));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CashConfig {
+    /// Unlimited if absent.
+    pub initial: Option<f64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum StrategyConfig {
+    Dca {
+        amount: f64,
+        interval: usize,
+    },
+    MovingAverage {
+        amount: f64,
+        interval: usize,
+        window: usize,
+    },
+}
+
+impl StrategyConfig {
+    pub fn build(&self) -> Box<dyn Strategy> {
+        match *self {
+            StrategyConfig::Dca { amount, interval } => Box::new(Dca::new(amount, interval)),
+            StrategyConfig::MovingAverage {
+                amount,
+                interval,
+                window,
+            } => Box::new(MovingAverage::new(amount, interval, window)),
+        }
+    }
+
+    /// The number of past records the strategy looks at.
+    pub fn lookback(&self) -> usize {
+        match *self {
+            StrategyConfig::Dca { .. } => 0,
+            StrategyConfig::MovingAverage { window, .. } => window,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BenchmarkConfig {
+    pub path: PathBuf,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct OutputConfig {
+    /// The JSON report is printed to stdout if absent.
+    pub path: Option<PathBuf>,
+}
+
+impl Config {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let mut config: Config = toml::from_str(&fs::read_to_string(path)?)?;
+        let base = path.parent().unwrap_or_else(|| Path::new(""));
+        config.rule.resolve(base)?;
+        if let Some(data) = &mut config.data.path {
+            *data = base.join(&data);
+        }
+        if let Some(benchmark) = &mut config.benchmark {
+            benchmark.path = base.join(&benchmark.path);
+        }
+        if let Some(batch) = &mut config.batch {
+            batch.directory = base.join(&batch.directory);
+        }
+        if let Some(share_classes) = &mut config.share_classes {
+            share_classes.a.path = base.join(&share_classes.a.path);
+            share_classes.c.path = base.join(&share_classes.c.path);
+            share_classes.a.rule.resolve(base)?;
+            share_classes.c.rule.resolve(base)?;
+        }
+        if let Some(stream) = &mut config.stream {
+            stream.listen.resolve(base);
+        }
+        if let Some(output) = &mut config.output.path {
+            *output = base.join(&output);
+        }
+        Ok(config)
+    }
+
+    pub fn history(&self) -> Result<Vec<(NaiveDate, f64)>> {
+        data::load(self.data.path.as_ref().ok_or(Error::Invalid)?)
+    }
+
+    /// A fresh repository over the configured date range of `history`.
+    pub fn repository(&self, history: Vec<(NaiveDate, f64)>) -> Result<Repository> {
+        let repository = Repository::new(self.rule.build()?, history)?.with_range(
+            self.data.start,
+            self.data.end,
+            self.data
+                .warm_up
+                .unwrap_or_else(|| self.strategy.lookback()),
+        )?;
+        Ok(match self.cash.initial {
+            Some(cash) => repository.with_cash(cash),
+            None => repository,
+        })
+    }
+
+    /// Runs the strategy over a fresh repository.
+    pub fn run(&self, history: Vec<(NaiveDate, f64)>) -> Result<Repository> {
+        self.run_observed(history, &mut |_| Ok(()))
+    }
+
+    /// Like `run`, calling `observer` after each day.
+    pub fn run_observed(
+        &self,
+        history: Vec<(NaiveDate, f64)>,
+        observer: &mut dyn FnMut(&Repository) -> Result<()>,
+    ) -> Result<Repository> {
+        let mut repository = self.repository(history)?;
+        backtest::run_observed(&mut repository, self.strategy.build().as_mut(), observer)?;
+        Ok(repository)
+    }
+}
@@ -0,0 +1,59 @@
+use crate::error::{Error, Result};
+use chrono::NaiveDate;
+use std::{
+    fs::File,
+    io::{BufRead, BufReader},
+    path::Path,
+};
+
+/// Loads a net asset value history from a `date,nav` CSV file.
+///
+/// A header line is skipped and the rows are sorted by date, so files exported
+/// newest-first can be used as they are.
+pub fn load<P: AsRef<Path>>(path: P) -> Result<Vec<(NaiveDate, f64)>> {
+    let reader = BufReader::new(File::open(path)?);
+    let mut history = vec![];
+    for (i, line) in reader.lines().enumerate() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        match parse(line) {
+            Some(record) => history.push(record),
+            None if i == 0 => continue,
+            None => return Err(Error::Invalid),
+        }
+    }
+    history.sort_by_key(|&(date, _)| date);
+    if history.windows(2).any(|w| w[0].0 == w[1].0) {
+        Err(Error::Invalid)
+    } else {
+        Ok(history)
+    }
+}
+
+fn parse(line: &str) -> Option<(NaiveDate, f64)> {
+    let mut fields = line.split(',').map(str::trim);
+    let date = fields.next()?.parse().ok()?;
+    let nav: f64 = fields.next()?.parse().ok()?;
+    if nav > 0. {
+        Some((date, nav))
+    } else {
+        None
+    }
+}
+
+/// Keeps the records within `[start, end]`, both ends optional.
+pub fn between(
+    history: &[(NaiveDate, f64)],
+    start: Option<NaiveDate>,
+    end: Option<NaiveDate>,
+) -> Vec<(NaiveDate, f64)> {
+    history
+        .iter()
+        .filter(|&&(date, _)| start.is_none_or(|start| start <= date))
+        .filter(|&&(date, _)| end.is_none_or(|end| date <= end))
+        .copied()
+        .collect()
+}
@@ -6,8 +6,14 @@ pub type Result<T> = std::result::Result<T, Error>;
 pub enum Error {
     Insufficient,
     Overflow,
+    Invalid,
     #[from]
     IO(std::io::Error),
+    /// The message of an `iced::Error`, which is not `Send`.
+    #[error(ignore)]
+    GUI(String),
     #[from]
-    GUI(iced::Error),
+    Toml(toml::de::Error),
+    #[from]
+    Json(serde_json::Error),
 }
@@ -0,0 +1,186 @@
+use crate::{
+    error::{Error, Result},
+    repository::{Leg, Order, Repository, Transaction},
+};
+
+/// Funds of one company that can be switched between (基金转换), each with its
+/// own repository on the same dates.
+#[derive(Debug)]
+pub struct Family {
+    funds: Vec<(String, Repository)>,
+}
+
+impl Family {
+    pub fn new(funds: Vec<(String, Repository)>) -> Result<Self> {
+        for (i, (name, _)) in funds.iter().enumerate() {
+            if funds[..i].iter().any(|(other, _)| other == name) {
+                return Err(Error::Invalid);
+            }
+        }
+        Ok(Self { funds })
+    }
+
+    pub fn funds(&self) -> &[(String, Repository)] {
+        &self.funds
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Repository> {
+        self.position(name).ok().map(|i| &self.funds[i].1)
+    }
+
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut Repository> {
+        match self.position(name) {
+            Ok(i) => Some(&mut self.funds[i].1),
+            Err(_) => None,
+        }
+    }
+
+    fn position(&self, name: &str) -> Result<usize> {
+        self.funds
+            .iter()
+            .position(|(other, _)| other == name)
+            .ok_or(Error::Invalid)
+    }
+
+    /// The fee of switching `share` from `from` to `to` today: the redemption
+    /// fee of `from` plus the difference of the investment fees, if `to`
+    /// charges more (申购补差).
+    pub fn switch_fee(&self, from: &str, to: &str, share: f64) -> Result<f64> {
+        let (from, to) = (
+            &self.funds[self.position(from)?].1,
+            &self.funds[self.position(to)?].1,
+        );
+        let (date, net_asset_value) = from.check()?;
+        let redemption_fee = from
+            .rule()
+            .quote(Order::SwitchOut {
+                date,
+                net_asset_value,
+                redemption: share,
+            })
+            .ok_or(Error::Invalid)?;
+        let money = share * net_asset_value - redemption_fee;
+        let difference = to.investment_fee(money)? - from.investment_fee(money)?;
+        Ok(redemption_fee + difference.max(0.))
+    }
+
+    /// Moves `share` from `from` to `to`, both confirmed at the net asset values
+    /// of the current day, which must be the same in both funds. The money
+    /// moved counts against the cash of `to` as an investment would.
+    pub fn switch(&mut self, from: &str, to: &str, share: f64) -> Result<()> {
+        let (i, j) = (self.position(from)?, self.position(to)?);
+        if i == j {
+            return Err(Error::Invalid);
+        }
+        let (date, from_net_asset_value) = self.funds[i].1.check()?;
+        let (to_date, to_net_asset_value) = self.funds[j].1.check()?;
+        if date != to_date {
+            return Err(Error::Invalid);
+        }
+        let fee = self.switch_fee(from, to, share)?;
+        let money = share * from_net_asset_value - fee;
+        let transaction = Transaction::Switch {
+            date,
+            from: Leg {
+                fund: String::from(from),
+                net_asset_value: from_net_asset_value,
+                share,
+            },
+            to: Leg {
+                fund: String::from(to),
+                net_asset_value: to_net_asset_value,
+                share: money / to_net_asset_value,
+            },
+            money,
+            fee,
+        };
+        // `switch_in` cannot fail once the outgoing leg is recorded.
+        if self.funds[j].1.cash() < money {
+            return Err(Error::Insufficient);
+        }
+        self.funds[i].1.switch_out(transaction.clone())?;
+        self.funds[j].1.switch_in(transaction)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::Fifo;
+    use chrono::NaiveDate;
+
+    #[test]
+    fn test_switch() {
+        let repository = |investment_rate, net_asset_value| {
+            Repository::new(
+                Box::new(Fifo::new(
+                    vec![(f64::INFINITY, investment_rate)],
+                    vec![(7, 0.015), (30, 0.005)],
+                )),
+                NaiveDate::from_ymd(2021, 1, 1)
+                    .iter_days()
+                    .take(20)
+                    .map(|date| (date, net_asset_value))
+                    .collect(),
+            )
+            .unwrap()
+        };
+        let mut family = Family::new(vec![
+            (String::from("bond"), repository(0.006, 1.0)),
+            (String::from("stock"), repository(0.015, 2.0)),
+        ])
+        .unwrap();
+        assert!(family.get_mut("bond").unwrap().invest(10_000.0).is_ok());
+        assert!(family.get_mut("stock").unwrap().pass().is_ok());
+        for _ in 0..9 {
+            assert!(family.get_mut("bond").unwrap().pass().is_ok());
+            assert!(family.get_mut("stock").unwrap().pass().is_ok());
+        }
+        // 0.5% after 10 days plus the 0.9% difference on the 7960 left
+        let fee = family.switch_fee("bond", "stock", 8000.0).unwrap();
+        assert!((fee - (40.0 + 7960.0 * 0.009)).abs() < 1e-9);
+        assert!(family.switch("bond", "stock", 8000.0).is_ok());
+        let bond = family.get("bond").unwrap();
+        let stock = family.get("stock").unwrap();
+        assert_eq!(bond.transactions().last(), stock.transactions().last());
+        assert!((bond.daily_infos()[10].holding_share() - 1940.0).abs() < 1e-9);
+        assert!((stock.daily_infos()[10].holding_share() - (8000.0 - fee) / 2.0).abs() < 1e-9);
+        assert!(
+            (bond.daily_infos()[10].cumulative_redemption()
+                - stock.daily_infos()[10].cumulative_investment())
+            .abs()
+                < 1e-9
+        );
+        // the switched shares start a new holding period
+        assert!((family.get("stock").unwrap().redemption_fee(100.0).unwrap() - 3.0).abs() < 1e-9);
+        assert!(
+            if let Err(Error::Insufficient) = family.switch("bond", "stock", 8000.0) {
+                true
+            } else {
+                false
+            }
+        );
+        assert!(family.get("bond").unwrap().daily_infos().len() == 11);
+
+        let mut family = Family::new(vec![
+            (String::from("bond"), repository(0.006, 1.0)),
+            (
+                String::from("stock"),
+                repository(0.015, 2.0).with_cash(1000.0),
+            ),
+        ])
+        .unwrap();
+        assert!(family.get_mut("bond").unwrap().invest(10_000.0).is_ok());
+        assert!(family.get_mut("stock").unwrap().pass().is_ok());
+        assert!(
+            if let Err(Error::Insufficient) = family.switch("bond", "stock", 8000.0) {
+                true
+            } else {
+                false
+            }
+        );
+        assert_eq!(family.get("bond").unwrap().daily_infos().len(), 1);
+        assert!(family.switch("bond", "stock", 1000.0).is_ok());
+        assert!(family.get("stock").unwrap().cash() >= 0.0);
+    }
+}
@@ -79,6 +79,10 @@ impl Application for Gui {
                 info.holding_price() * info.holding_share() + info.cumulative_redemption()
                     - info.cumulative_investment()
             )))
+            .push(Self::text(format!(
+                "累计运作费用: {:.2}",
+                info.cumulative_accrued_fee()
+            )))
             .push(slider)
             .into()
     }
@@ -1,5 +1,21 @@
+pub mod backtest;
+pub mod batch;
+pub mod client;
+pub mod config;
+pub mod data;
 pub mod error;
+pub mod family;
 pub mod gui;
+pub mod metrics;
+pub mod report;
 pub mod repository;
+pub mod rolling;
 pub mod rules;
+pub mod schedule;
 pub mod server;
+pub mod share_class;
+pub mod strategies;
+pub mod stream;
+pub mod sweep;
+pub mod synthetic;
+pub mod walk_forward;
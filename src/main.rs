@@ -1,8 +1,45 @@
 use chrono::NaiveDate;
-use fund::{error::Result, gui::Gui, server::Server};
+use fund::{
+    batch,
+    config::Config,
+    error::{Error, Result},
+    gui::Gui,
+    report::{Export, Report},
+    rolling::Rolling,
+    server::{record, Server, ServerConfig},
+    share_class::Comparison,
+    stream::Stream,
+    sweep,
+    synthetic::MonteCarlo,
+    walk_forward::WalkForward,
+};
 use iced::{Application, Settings};
+use std::sync::Arc;
+use tokio::sync::watch;
 
 fn main() -> Result<()> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+        [] => demo(),
+        ["run", path] => run(path),
+        ["sweep", path] => sweep(path),
+        ["walk-forward", path] => walk_forward(path),
+        ["rolling", path] => rolling(path),
+        ["monte-carlo", path] => monte_carlo(path),
+        ["batch", path] => batch(path),
+        ["share-classes", path] => share_classes(path),
+        ["serve", path] => serve(path),
+        ["replay", path] => replay(path),
+        _ => {
+            eprintln!(
+                "usage: fund [run|sweep|walk-forward|rolling|monte-carlo|batch|share-classes|serve <config.toml>|replay <log.jsonl>]"
+            );
+            Err(Error::Invalid)
+        }
+    }
+}
+
+fn demo() -> Result<()> {
     Gui::run(Settings::with_flags(Server::run(
         Box::new(|_| 0.0),
         NaiveDate::from_ymd(2021, 1, 1)
@@ -11,7 +48,151 @@ fn main() -> Result<()> {
             .take(5)
             .map(|(i, date)| (date, if i & 1 == 0 { 1.0 } else { 1.05 }))
             .collect(),
-        8000,
+        std::net::TcpListener::bind(("127.0.0.1", 8000))?,
     )?))
-    .map_err(|err| err.into())
+    .map_err(|err| Error::GUI(err.to_string()))
+}
+
+fn run(path: &str) -> Result<()> {
+    let config = Config::load(path)?;
+    let history = config.history()?;
+    let repository = match &config.stream {
+        Some(stream) => tokio::runtime::Runtime::new()?.block_on(async {
+            let listener = stream.listen.bind().await?;
+            // stdout is for the report
+            stream
+                .listen
+                .announce(std::io::stderr(), &[("stream", &listener)])?;
+            let mut live = Stream::new(listener, history.len());
+            if stream.wait {
+                live.subscribed().await;
+            }
+            let repository =
+                config.run_observed(history, &mut |repository| live.publish(repository));
+            live.close().await;
+            if let Some(path) = &stream.listen.unix {
+                std::fs::remove_file(path)?;
+            }
+            repository
+        })?,
+        None => config.run(history)?,
+    };
+    Report::new(&config, &repository)?.write()
+}
+
+fn sweep(path: &str) -> Result<()> {
+    let config = Config::load(path)?;
+    let trials = sweep::sweep(
+        &config,
+        config.sweep.as_ref().ok_or(Error::Invalid)?,
+        &config.history()?,
+    )?;
+    Export::new(&config, &trials).write()?;
+    if config.output.path.is_some() {
+        sweep::write_table(std::io::stdout(), &trials)?;
+    }
+    Ok(())
+}
+
+fn walk_forward(path: &str) -> Result<()> {
+    let config = Config::load(path)?;
+    let walk_forward = WalkForward::new(
+        &config,
+        config.sweep.as_ref().ok_or(Error::Invalid)?,
+        config.walk_forward.as_ref().ok_or(Error::Invalid)?,
+        &config.history()?,
+    )?;
+    Export::new(&config, &walk_forward).write()
+}
+
+fn rolling(path: &str) -> Result<()> {
+    let config = Config::load(path)?;
+    let rolling = Rolling::new(
+        &config,
+        config.rolling.as_ref().ok_or(Error::Invalid)?,
+        &config.history()?,
+    )?;
+    Export::new(&config, &rolling).write()
+}
+
+fn monte_carlo(path: &str) -> Result<()> {
+    let config = Config::load(path)?;
+    let monte_carlo = MonteCarlo::new(
+        &config,
+        config.monte_carlo.as_ref().ok_or(Error::Invalid)?,
+        &config.history()?,
+    )?;
+    Export::new(&config, &monte_carlo).write()
+}
+
+fn batch(path: &str) -> Result<()> {
+    let config = Config::load(path)?;
+    let funds = batch::batch(&config, config.batch.as_ref().ok_or(Error::Invalid)?)?;
+    Export::new(&config, &funds).write()?;
+    if config.output.path.is_some() {
+        batch::write_table(std::io::stdout(), &funds)?;
+    }
+    Ok(())
+}
+
+fn share_classes(path: &str) -> Result<()> {
+    let config = Config::load(path)?;
+    let comparison = Comparison::new(
+        &config,
+        config.share_classes.as_ref().ok_or(Error::Invalid)?,
+    )?;
+    Export::new(&config, &comparison).write()
+}
+
+fn serve(path: &str) -> Result<()> {
+    let config = ServerConfig::load(path)?;
+    let serve = config.server.clone();
+    let server = Arc::new(Server::new(config)?);
+    tokio::runtime::Runtime::new()?.block_on(async {
+        let listener = serve.listen.bind().await?;
+        let http = serve.bind_http().await?;
+        let mut listeners = vec![("line", &listener)];
+        if let Some(http) = &http {
+            listeners.push(("http", http));
+        }
+        serve.listen.announce(std::io::stdout(), &listeners)?;
+        let (notify, closing) = watch::channel(false);
+        tokio::spawn(async move {
+            tokio::signal::ctrl_c().await.ok();
+            notify.send(true).ok();
+        });
+        let shutdown = |mut closing: watch::Receiver<bool>| async move {
+            closing.changed().await.ok();
+        };
+        let http = async {
+            match http {
+                Some(listener) => {
+                    server
+                        .clone()
+                        .serve_http(listener, shutdown(closing.clone()))
+                        .await
+                }
+                None => Ok(()),
+            }
+        };
+        tokio::try_join!(
+            server.clone().serve(listener, shutdown(closing.clone())),
+            http
+        )
+        .map(|_| ())
+    })?;
+    if let Some(path) = &serve.listen.unix {
+        std::fs::remove_file(path)?;
+    }
+    Ok(())
+}
+
+fn replay(path: &str) -> Result<()> {
+    match record::replay(path)? {
+        record::Outcome::Replayed => Ok(()),
+        outcome => {
+            println!("{}", serde_json::to_string_pretty(&outcome)?);
+            Err(Error::Invalid)
+        }
+    }
 }
@@ -0,0 +1,256 @@
+use crate::repository::Repository;
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Metric {
+    Profit,
+    TotalReturn,
+    Xirr,
+    MaxDrawdown,
+}
+
+impl Metric {
+    /// Whether a larger value of the metric is better.
+    pub fn is_ascending(self) -> bool {
+        !matches!(self, Metric::MaxDrawdown)
+    }
+
+    /// Orders values best first, leaving undefined values last.
+    pub fn compare(self, a: f64, b: f64) -> Ordering {
+        match (a.is_nan(), b.is_nan()) {
+            (true, true) => Ordering::Equal,
+            (true, false) => Ordering::Greater,
+            (false, true) => Ordering::Less,
+            (false, false) if self.is_ascending() => b.partial_cmp(&a).unwrap(),
+            (false, false) => a.partial_cmp(&b).unwrap(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Metrics {
+    pub investment: f64,
+    pub redemption: f64,
+    pub value: f64,
+    pub profit: f64,
+    pub total_return: f64,
+    pub xirr: f64,
+    pub max_drawdown: f64,
+}
+
+impl Metrics {
+    pub fn new(repository: &Repository) -> Self {
+        let info = repository.daily_infos().last();
+        let investment = info.map_or(0., |info| info.cumulative_investment());
+        let redemption = info.map_or(0., |info| info.cumulative_redemption());
+        let value = value(repository);
+        let profit = value + redemption - investment;
+        let capital = capital(repository);
+        let equity: Vec<f64> = equity_curve(repository)
+            .into_iter()
+            .map(|(_, equity)| equity)
+            .collect();
+        Self {
+            investment,
+            redemption,
+            value,
+            profit,
+            total_return: if capital > 0. { profit / capital } else { 0. },
+            xirr: xirr(&cash_flows(repository)),
+            max_drawdown: max_drawdown(&equity),
+        }
+    }
+
+    pub fn get(&self, metric: Metric) -> f64 {
+        match metric {
+            Metric::Profit => self.profit,
+            Metric::TotalReturn => self.total_return,
+            Metric::Xirr => self.xirr,
+            Metric::MaxDrawdown => self.max_drawdown,
+        }
+    }
+}
+
+/// The value of the holding at the last simulated day.
+fn value(repository: &Repository) -> f64 {
+    let days = repository.daily_infos().len();
+    if days == 0 {
+        0.
+    } else {
+        repository.daily_infos()[days - 1].holding_share()
+            * repository.net_asset_value_history()[days - 1].1
+    }
+}
+
+/// The money put at stake: the initial cash, or everything invested when cash
+/// is unlimited.
+pub fn capital(repository: &Repository) -> f64 {
+    if repository.initial_cash().is_finite() {
+        repository.initial_cash()
+    } else {
+        repository
+            .daily_infos()
+            .last()
+            .map_or(0., |info| info.cumulative_investment())
+    }
+}
+
+/// Daily total assets, i.e. the capital plus the cumulative profit.
+pub fn equity_curve(repository: &Repository) -> Vec<(NaiveDate, f64)> {
+    let capital = capital(repository);
+    repository
+        .net_asset_value_history()
+        .iter()
+        .zip(repository.daily_infos())
+        .map(|(&(date, nav), info)| {
+            (
+                date,
+                capital + info.holding_share() * nav + info.cumulative_redemption()
+                    - info.cumulative_investment(),
+            )
+        })
+        .collect()
+}
+
+/// Money put in as outflows, money taken out and the final holding value as
+/// inflows, switches included.
+pub fn cash_flows(repository: &Repository) -> Vec<(NaiveDate, f64)> {
+    let mut last = (0., 0.);
+    let mut flows: Vec<(NaiveDate, f64)> = vec![];
+    for (info, &(date, _)) in repository
+        .daily_infos()
+        .iter()
+        .zip(repository.net_asset_value_history())
+    {
+        let current = (info.cumulative_investment(), info.cumulative_redemption());
+        if info.transaction_id().is_some() {
+            flows.push((date, (current.1 - last.1) - (current.0 - last.0)));
+        }
+        last = current;
+    }
+    let days = repository.daily_infos().len();
+    if days > 0 {
+        let (date, _) = repository.net_asset_value_history()[days - 1];
+        flows.push((date, value(repository)));
+    }
+    flows
+}
+
+/// The annualized internal rate of return of dated cash flows, or NaN if it
+/// does not exist.
+pub fn xirr(flows: &[(NaiveDate, f64)]) -> f64 {
+    let start = match flows.iter().map(|&(date, _)| date).min() {
+        Some(start) => start,
+        None => return f64::NAN,
+    };
+    let npv = |rate: f64| {
+        flows
+            .iter()
+            .map(|&(date, flow)| flow / (1. + rate).powf((date - start).num_days() as f64 / 365.))
+            .sum::<f64>()
+    };
+    let (mut low, mut high) = (-0.9999, 1.);
+    while npv(high) > 0. && high < 1e6 {
+        high *= 2.;
+    }
+    if npv(low).signum() == npv(high).signum() {
+        return f64::NAN;
+    }
+    for _ in 0..200 {
+        let mid = (low + high) / 2.;
+        if npv(mid).signum() == npv(low).signum() {
+            low = mid;
+        } else {
+            high = mid;
+        }
+    }
+    (low + high) / 2.
+}
+
+/// The largest relative decline from a running peak.
+pub fn max_drawdown(values: &[f64]) -> f64 {
+    values
+        .iter()
+        .scan(f64::NEG_INFINITY, |peak, &value| {
+            *peak = peak.max(value);
+            Some(if *peak > 0. { 1. - value / *peak } else { 0. })
+        })
+        .fold(0., f64::max)
+}
+
+/// Summary statistics of a sample, ignoring undefined values.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Distribution {
+    pub count: usize,
+    pub mean: f64,
+    pub min: f64,
+    pub p5: f64,
+    pub p25: f64,
+    pub median: f64,
+    pub p75: f64,
+    pub p95: f64,
+    pub max: f64,
+}
+
+impl Distribution {
+    pub fn new(values: &[f64]) -> Self {
+        let mut values: Vec<f64> = values.iter().copied().filter(|x| !x.is_nan()).collect();
+        values.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        let percentile = |p: f64| {
+            if values.is_empty() {
+                return f64::NAN;
+            }
+            let rank = p * (values.len() - 1) as f64;
+            let (low, high) = (rank.floor() as usize, rank.ceil() as usize);
+            values[low] + (values[high] - values[low]) * (rank - low as f64)
+        };
+        Self {
+            count: values.len(),
+            mean: values.iter().sum::<f64>() / values.len() as f64,
+            min: percentile(0.),
+            p5: percentile(0.05),
+            p25: percentile(0.25),
+            median: percentile(0.5),
+            p75: percentile(0.75),
+            p95: percentile(0.95),
+            max: percentile(1.),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_xirr() {
+        let flows = [
+            (NaiveDate::from_ymd(2020, 1, 1), -100.0),
+            (NaiveDate::from_ymd(2020, 12, 31), 110.0),
+        ];
+        assert!((xirr(&flows) - 0.1).abs() < 1e-6);
+        assert!(xirr(&flows[..1]).is_nan());
+    }
+
+    #[test]
+    fn test_max_drawdown() {
+        assert_eq!(max_drawdown(&[]), 0.);
+        assert_eq!(max_drawdown(&[1.0, 2.0, 1.5, 3.0, 1.5, 2.0]), 0.5);
+    }
+
+    #[test]
+    fn test_distribution() {
+        let distribution = Distribution::new(&[5.0, f64::NAN, 1.0, 3.0, 2.0, 4.0]);
+        assert_eq!(distribution.count, 5);
+        assert_eq!(distribution.mean, 3.0);
+        assert_eq!(distribution.min, 1.0);
+        assert_eq!(distribution.p25, 2.0);
+        assert_eq!(distribution.median, 3.0);
+        assert!((distribution.p95 - 4.8).abs() < 1e-9);
+        assert_eq!(distribution.max, 5.0);
+        assert!(Distribution::new(&[]).median.is_nan());
+    }
+}
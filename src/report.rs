@@ -0,0 +1,98 @@
+use crate::{
+    config::Config,
+    data,
+    error::Result,
+    metrics::{self, Metrics},
+    repository::{Repository, Transaction},
+};
+use chrono::NaiveDate;
+use serde::Serialize;
+use std::{fs::File, io::BufWriter};
+
+/// The exported result of a run, carrying the configuration that produced it.
+#[derive(Debug, Serialize)]
+pub struct Report<'a> {
+    version: &'static str,
+    config: &'a Config,
+    metrics: Metrics,
+    benchmark: Option<BenchmarkMetrics>,
+    transactions: &'a [Transaction],
+    equity_curve: Vec<(NaiveDate, f64)>,
+}
+
+/// Buy-and-hold performance of the benchmark over the simulated dates.
+#[derive(Debug, Serialize)]
+pub struct BenchmarkMetrics {
+    total_return: f64,
+    max_drawdown: f64,
+}
+
+impl BenchmarkMetrics {
+    pub fn new(history: &[(NaiveDate, f64)], start: NaiveDate, end: NaiveDate) -> Option<Self> {
+        let navs: Vec<f64> = data::between(history, Some(start), Some(end))
+            .into_iter()
+            .map(|(_, nav)| nav)
+            .collect();
+        Some(Self {
+            total_return: navs.last()? / navs.first()? - 1.,
+            max_drawdown: metrics::max_drawdown(&navs),
+        })
+    }
+}
+
+impl<'a> Report<'a> {
+    pub fn new(config: &'a Config, repository: &'a Repository) -> Result<Self> {
+        let equity_curve = metrics::equity_curve(repository);
+        let benchmark = match (&config.benchmark, equity_curve.first(), equity_curve.last()) {
+            (Some(benchmark), Some(&(start, _)), Some(&(end, _))) => {
+                BenchmarkMetrics::new(&data::load(&benchmark.path)?, start, end)
+            }
+            _ => None,
+        };
+        Ok(Self {
+            version: env!("CARGO_PKG_VERSION"),
+            config,
+            metrics: Metrics::new(repository),
+            benchmark,
+            transactions: repository.transactions(),
+            equity_curve,
+        })
+    }
+
+    /// Writes the report to the configured output, or stdout.
+    pub fn write(&self) -> Result<()> {
+        write(self.config, self)
+    }
+}
+
+/// The result of any other mode together with the configuration that
+/// produced it.
+#[derive(Debug, Serialize)]
+pub struct Export<'a, T> {
+    version: &'static str,
+    config: &'a Config,
+    result: T,
+}
+
+impl<'a, T: Serialize> Export<'a, T> {
+    pub fn new(config: &'a Config, result: T) -> Self {
+        Self {
+            version: env!("CARGO_PKG_VERSION"),
+            config,
+            result,
+        }
+    }
+
+    pub fn write(&self) -> Result<()> {
+        write(self.config, self)
+    }
+}
+
+/// Writes `value` as JSON to the configured output, or stdout.
+pub fn write<T: Serialize>(config: &Config, value: &T) -> Result<()> {
+    match &config.output.path {
+        Some(path) => serde_json::to_writer_pretty(BufWriter::new(File::create(path)?), value)?,
+        None => serde_json::to_writer_pretty(std::io::stdout(), value)?,
+    }
+    Ok(())
+}
@@ -1,7 +1,9 @@
 use crate::error::{Error, Result};
 use chrono::NaiveDate;
+use serde::Serialize;
+use std::collections::VecDeque;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub enum Order {
     Investment {
         date: NaiveDate,
@@ -13,9 +15,35 @@ pub enum Order {
         net_asset_value: f64,
         redemption: f64,
     },
+    /// The outgoing leg of a switch to another fund of the family (转换转出),
+    /// charged instead of a redemption.
+    SwitchOut {
+        date: NaiveDate,
+        net_asset_value: f64,
+        redemption: f64,
+    },
+    /// The incoming leg of a switch from another fund, bought with the money of
+    /// the outgoing leg.
+    SwitchIn {
+        date: NaiveDate,
+        net_asset_value: f64,
+        share: f64,
+    },
 }
 
-#[derive(Debug, PartialEq)]
+impl Order {
+    pub fn date(&self) -> NaiveDate {
+        match *self {
+            Order::Investment { date, .. }
+            | Order::Redemption { date, .. }
+            | Order::SwitchOut { date, .. }
+            | Order::SwitchIn { date, .. } => date,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
 pub enum Transaction {
     Investment {
         date: NaiveDate,
@@ -31,10 +59,52 @@ pub enum Transaction {
         money: f64,
         fee: f64,
     },
+    /// A switch between two funds of a family, recorded by both.
+    Switch {
+        date: NaiveDate,
+        from: Leg,
+        to: Leg,
+        /// The money moved into `to`.
+        money: f64,
+        fee: f64,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Leg {
+    pub fund: String,
+    pub net_asset_value: f64,
+    pub share: f64,
 }
 
-pub trait Rule {
+/// Rules are `Send` so that repositories can move between threads, e.g. the
+/// sessions of `server::Server`.
+pub trait Rule: Send {
     fn fee(&mut self, order: Order) -> f64;
+
+    /// The recurring fees on `value`, the closing holding value of `date`, for
+    /// the `days` until the next record. Zero by default, as real net asset
+    /// values are published after these fees.
+    fn accrual(&mut self, _date: NaiveDate, _days: i64, _value: f64) -> f64 {
+        0.
+    }
+
+    /// Scales the shares kept by the rule, e.g. the lots of a `Fifo`, by
+    /// `ratio` once the `accrual` is taken out of the holding share.
+    fn accrue(&mut self, _ratio: f64) {}
+
+    /// A copy of the rule in its current state, e.g. the lots of a `Fifo`, for
+    /// `Repository::undo`. The state is restored by replacing the rule with the
+    /// copy. None by default, for rules that cannot be copied.
+    fn snapshot(&self) -> Option<Box<dyn Rule>> {
+        None
+    }
+
+    /// The fee of `order` without changing the state, on a snapshot by
+    /// default.
+    fn quote(&self, order: Order) -> Option<f64> {
+        self.snapshot().map(|mut rule| rule.fee(order))
+    }
 }
 
 impl std::fmt::Debug for dyn Rule {
@@ -43,22 +113,32 @@ impl std::fmt::Debug for dyn Rule {
     }
 }
 
+/// A closure has no state of its own, so its snapshot is a copy of it.
 impl<F> Rule for F
 where
-    F: Fn(Order) -> f64,
+    F: Fn(Order) -> f64 + Clone + Send + 'static,
 {
     fn fee(&mut self, order: Order) -> f64 {
         self(order)
     }
+
+    fn snapshot(&self) -> Option<Box<dyn Rule>> {
+        Some(Box::new(self.clone()))
+    }
+
+    fn quote(&self, order: Order) -> Option<f64> {
+        Some(self(order))
+    }
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize)]
 pub struct DailyInfo {
     transaction_id: usize,
     holding_price: f64,
     holding_share: f64,
     cumulative_investment: f64,
     cumulative_redemption: f64,
+    cumulative_accrued_fee: f64,
 }
 
 impl DailyInfo {
@@ -85,14 +165,26 @@ impl DailyInfo {
     pub fn cumulative_redemption(&self) -> f64 {
         self.cumulative_redemption
     }
+
+    pub fn cumulative_accrued_fee(&self) -> f64 {
+        self.cumulative_accrued_fee
+    }
 }
 
 #[derive(Debug)]
 pub struct Repository {
     rule: Box<dyn Rule>,
     net_asset_value_history: Vec<(NaiveDate, f64)>,
+    warm_up: usize,
+    initial_cash: f64,
     transactions: Vec<Transaction>,
     daily_infos: Vec<DailyInfo>,
+    /// The most days `undo` can revert, none by default.
+    undo: usize,
+    /// The rule before each of the last `undo` days, for `undo`.
+    snapshots: VecDeque<Box<dyn Rule>>,
+    /// The rule before the first day, for `restart`.
+    initial: Option<Box<dyn Rule>>,
 }
 
 impl Repository {
@@ -104,6 +196,8 @@ impl Repository {
             Ok(Repository {
                 rule,
                 net_asset_value_history,
+                warm_up: 0,
+                initial_cash: f64::INFINITY,
                 transactions: vec![],
                 daily_infos: vec![DailyInfo {
                     transaction_id: 0,
@@ -111,19 +205,84 @@ impl Repository {
                     holding_share: 0.0,
                     cumulative_investment: 0.0,
                     cumulative_redemption: 0.0,
+                    cumulative_accrued_fee: 0.0,
                 }],
+                undo: 0,
+                snapshots: VecDeque::new(),
+                initial: None,
             })
         } else {
             Err(Error::Insufficient)
         }
     }
 
+    /// Limits the money available for investment, which is unlimited by default.
+    pub fn with_cash(mut self, cash: f64) -> Self {
+        self.initial_cash = cash;
+        self
+    }
+
+    /// Keeps the rule before each of the last `depth` days for `undo`, and
+    /// before the first one for `restart`, if the rule has a `snapshot`.
+    ///
+    /// Must be called before the first day is simulated.
+    pub fn with_undo(mut self, depth: usize) -> Self {
+        self.initial = self.rule.snapshot();
+        self.undo = if self.initial.is_some() { depth } else { 0 };
+        self
+    }
+
+    /// Restricts trading to `[start, end]`, both ends optional, keeping up to
+    /// `warm_up` earlier records visible through `warm_up` and `window` only.
+    ///
+    /// Must be called before the first day is simulated.
+    pub fn with_range(
+        mut self,
+        start: Option<NaiveDate>,
+        end: Option<NaiveDate>,
+        warm_up: usize,
+    ) -> Result<Self> {
+        if self.daily_infos.len() != 1 {
+            return Err(Error::Invalid);
+        }
+        if let Some(end) = end {
+            self.net_asset_value_history
+                .retain(|&(date, _)| date <= end);
+        }
+        let first = start.map_or(self.warm_up, |start| {
+            self.net_asset_value_history
+                .iter()
+                .position(|&(date, _)| start <= date)
+                .unwrap_or(self.net_asset_value_history.len())
+        });
+        self.warm_up = warm_up.min(first);
+        self.net_asset_value_history.drain(..first - self.warm_up);
+        if self.len() >= 1 {
+            Ok(self)
+        } else {
+            Err(Error::Insufficient)
+        }
+    }
+
     pub fn len(&self) -> usize {
-        self.net_asset_value_history.len()
+        self.net_asset_value_history().len()
     }
 
+    /// The records that can be traded on, in line with `daily_infos`.
     pub fn net_asset_value_history(&self) -> &[(NaiveDate, f64)] {
-        &self.net_asset_value_history
+        &self.net_asset_value_history[self.warm_up..]
+    }
+
+    /// The records before the first tradable day.
+    pub fn warm_up(&self) -> &[(NaiveDate, f64)] {
+        &self.net_asset_value_history[..self.warm_up]
+    }
+
+    /// The last `n` records up to the current day, including the warm-up.
+    pub fn window(&self, n: usize) -> &[(NaiveDate, f64)] {
+        let end =
+            (self.warm_up + self.daily_infos().len() + 1).min(self.net_asset_value_history.len());
+        &self.net_asset_value_history[end.saturating_sub(n)..end]
     }
 
     pub fn daily_infos(&self) -> &[DailyInfo] {
@@ -134,8 +293,21 @@ impl Repository {
         &self.transactions
     }
 
+    pub fn rule(&self) -> &dyn Rule {
+        self.rule.as_ref()
+    }
+
+    pub fn initial_cash(&self) -> f64 {
+        self.initial_cash
+    }
+
+    pub fn cash(&self) -> f64 {
+        let info = self.daily_infos.last().unwrap();
+        self.initial_cash - info.cumulative_investment + info.cumulative_redemption
+    }
+
     pub fn check(&self) -> Result<(NaiveDate, f64)> {
-        self.net_asset_value_history
+        self.net_asset_value_history()
             .get(self.daily_infos().len())
             .map(|&x| x)
             .ok_or(Error::Overflow)
@@ -147,19 +319,106 @@ impl Repository {
         } else {
             let mut info = self.daily_infos.last().unwrap().clone();
             info.transaction_id = 0;
-            self.daily_infos.push(info);
+            self.save();
+            self.push(info);
             Ok(())
         }
     }
 
+    /// Records the day after charging the recurring fees by reducing the
+    /// holding share, and the shares kept by the rule alike.
+    fn push(&mut self, mut info: DailyInfo) {
+        let i = self.warm_up + self.daily_infos.len() - 1;
+        let (date, net_asset_value) = self.net_asset_value_history[i];
+        let days = self
+            .net_asset_value_history
+            .get(i + 1)
+            .map_or(1, |&(next, _)| (next - date).num_days());
+        let fee = self
+            .rule
+            .accrual(date, days, info.holding_share * net_asset_value);
+        if fee != 0. {
+            let share = info.holding_share - fee / net_asset_value;
+            self.rule.accrue(share / info.holding_share);
+            info.holding_share = share;
+            info.cumulative_accrued_fee += fee;
+        }
+        self.daily_infos.push(info);
+    }
+
+    fn save(&mut self) {
+        if self.undo > 0 {
+            if self.snapshots.len() == self.undo {
+                self.snapshots.pop_front();
+            }
+            self.snapshots.extend(self.rule.snapshot());
+        }
+    }
+
+    /// The most days `undo` can revert.
+    pub fn undo_depth(&self) -> usize {
+        self.undo
+    }
+
+    /// Reverts the last day, whether passed or traded on.
+    pub fn undo(&mut self) -> Result<()> {
+        let rule = self.snapshots.pop_back().ok_or(Error::Insufficient)?;
+        let info = self.daily_infos.pop().unwrap();
+        if info.transaction_id != 0 {
+            self.transactions.pop();
+        }
+        self.rule = rule;
+        Ok(())
+    }
+
+    /// Reverts every day, starting the simulation over.
+    pub fn restart(&mut self) -> Result<()> {
+        self.rule = self
+            .initial
+            .as_ref()
+            .and_then(|rule| rule.snapshot())
+            .ok_or(Error::Invalid)?;
+        self.transactions.clear();
+        self.daily_infos.truncate(1);
+        self.snapshots.clear();
+        Ok(())
+    }
+
+    /// The fee of investing `investment` today, without investing.
+    pub fn investment_fee(&self, investment: f64) -> Result<f64> {
+        let (date, net_asset_value) = self.check()?;
+        self.rule
+            .quote(Order::Investment {
+                date,
+                net_asset_value,
+                investment,
+            })
+            .ok_or(Error::Invalid)
+    }
+
+    /// The fee of redeeming `redemption` shares today, without redeeming.
+    pub fn redemption_fee(&self, redemption: f64) -> Result<f64> {
+        let (date, net_asset_value) = self.check()?;
+        self.rule
+            .quote(Order::Redemption {
+                date,
+                net_asset_value,
+                redemption,
+            })
+            .ok_or(Error::Invalid)
+    }
+
     pub fn invest(&mut self, investment: f64) -> Result<()> {
         if self.len() == self.daily_infos().len() {
             Err(Error::Overflow)
+        } else if self.cash() < investment {
+            Err(Error::Insufficient)
         } else {
             let &(date, net_asset_value) = self
-                .net_asset_value_history
+                .net_asset_value_history()
                 .get(self.daily_infos.len() - 1)
                 .unwrap();
+            self.save();
             let fee = self.rule.fee(Order::Investment {
                 date,
                 net_asset_value,
@@ -179,7 +438,7 @@ impl Repository {
                 / (info.holding_share + share);
             info.holding_share += share;
             info.cumulative_investment += investment;
-            self.daily_infos.push(info);
+            self.push(info);
             Ok(())
         }
     }
@@ -191,9 +450,10 @@ impl Repository {
             Err(Error::Insufficient)
         } else {
             let &(date, net_asset_value) = self
-                .net_asset_value_history
+                .net_asset_value_history()
                 .get(self.daily_infos.len() - 1)
                 .unwrap();
+            self.save();
             let fee = self.rule.fee(Order::Redemption {
                 date,
                 net_asset_value,
@@ -211,15 +471,75 @@ impl Repository {
             info.transaction_id = self.transactions.len();
             info.holding_share -= redemption;
             info.cumulative_redemption += money;
-            self.daily_infos.push(info);
+            self.push(info);
             Ok(())
         }
     }
+
+    /// Records the outgoing leg of `transaction`, settled by `Family::switch`.
+    pub(crate) fn switch_out(&mut self, transaction: Transaction) -> Result<()> {
+        let (date, leg, money) = match &transaction {
+            Transaction::Switch {
+                date, from, money, ..
+            } => (*date, from.clone(), *money),
+            _ => return Err(Error::Invalid),
+        };
+        if self.check()?.0 != date {
+            return Err(Error::Invalid);
+        } else if self.daily_infos.last().unwrap().holding_share < leg.share {
+            return Err(Error::Insufficient);
+        }
+        self.save();
+        self.rule.fee(Order::SwitchOut {
+            date,
+            net_asset_value: leg.net_asset_value,
+            redemption: leg.share,
+        });
+        self.transactions.push(transaction);
+        let mut info = self.daily_infos.last().unwrap().clone();
+        info.transaction_id = self.transactions.len();
+        info.holding_share -= leg.share;
+        info.cumulative_redemption += money;
+        self.push(info);
+        Ok(())
+    }
+
+    /// Records the incoming leg of `transaction`, settled by `Family::switch`.
+    /// The money moved in is taken from the cash, as for an investment.
+    pub(crate) fn switch_in(&mut self, transaction: Transaction) -> Result<()> {
+        let (date, leg, money) = match &transaction {
+            Transaction::Switch {
+                date, to, money, ..
+            } => (*date, to.clone(), *money),
+            _ => return Err(Error::Invalid),
+        };
+        if self.check()?.0 != date {
+            return Err(Error::Invalid);
+        } else if self.cash() < money {
+            return Err(Error::Insufficient);
+        }
+        self.save();
+        self.rule.fee(Order::SwitchIn {
+            date,
+            net_asset_value: leg.net_asset_value,
+            share: leg.share,
+        });
+        self.transactions.push(transaction);
+        let mut info = self.daily_infos.last().unwrap().clone();
+        info.transaction_id = self.transactions.len();
+        info.holding_price =
+            (info.holding_price * info.holding_share + money) / (info.holding_share + leg.share);
+        info.holding_share += leg.share;
+        info.cumulative_investment += money;
+        self.push(info);
+        Ok(())
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::rules::Fifo;
 
     #[test]
     fn test_zero_fee() {
@@ -311,37 +631,185 @@ mod tests {
                     holding_price: 1.0,
                     holding_share: 100.0,
                     cumulative_investment: 100.0,
-                    cumulative_redemption: 0.0
+                    cumulative_redemption: 0.0,
+                    cumulative_accrued_fee: 0.0
                 },
                 DailyInfo {
                     transaction_id: 2,
                     holding_price: 1.0,
                     holding_share: 50.0,
                     cumulative_investment: 100.0,
-                    cumulative_redemption: 52.5
+                    cumulative_redemption: 52.5,
+                    cumulative_accrued_fee: 0.0
                 },
                 DailyInfo {
                     transaction_id: 0,
                     holding_price: 1.0,
                     holding_share: 50.0,
                     cumulative_investment: 100.0,
-                    cumulative_redemption: 52.5
+                    cumulative_redemption: 52.5,
+                    cumulative_accrued_fee: 0.0
                 },
                 DailyInfo {
                     transaction_id: 3,
                     holding_price: 100.0 / (50.0 + 50.0 / 1.05),
                     holding_share: 50.0 + 50.0 / 1.05,
                     cumulative_investment: 150.0,
-                    cumulative_redemption: 52.5
+                    cumulative_redemption: 52.5,
+                    cumulative_accrued_fee: 0.0
                 },
                 DailyInfo {
                     transaction_id: 4,
                     holding_price: 200.0 / (150.0 + 50.0 / 1.05),
                     holding_share: 150.0 + 50.0 / 1.05,
                     cumulative_investment: 250.0,
-                    cumulative_redemption: 52.5
+                    cumulative_redemption: 52.5,
+                    cumulative_accrued_fee: 0.0
                 }
             ]
         );
     }
+
+    #[test]
+    fn test_cash() {
+        let mut repo = Repository::new(
+            Box::new(|_| 0.0),
+            NaiveDate::from_ymd(2021, 1, 1)
+                .iter_days()
+                .take(3)
+                .map(|date| (date, 1.0))
+                .collect(),
+        )
+        .unwrap()
+        .with_cash(100.0);
+        assert!(repo.invest(60.0).is_ok());
+        assert_eq!(repo.cash(), 40.0);
+        assert!(if let Err(Error::Insufficient) = repo.invest(50.0) {
+            true
+        } else {
+            false
+        });
+        assert!(repo.redeem(10.0).is_ok());
+        assert_eq!(repo.cash(), 50.0);
+        assert!(repo.invest(50.0).is_ok());
+        assert_eq!(repo.cash(), 0.0);
+    }
+
+    #[test]
+    fn test_range() {
+        let history: Vec<_> = NaiveDate::from_ymd(2021, 1, 1)
+            .iter_days()
+            .enumerate()
+            .take(10)
+            .map(|(i, date)| (date, 1.0 + i as f64))
+            .collect();
+        let mut repo = Repository::new(Box::new(|_| 0.0), history.clone())
+            .unwrap()
+            .with_range(
+                Some(NaiveDate::from_ymd(2021, 1, 3)),
+                Some(NaiveDate::from_ymd(2021, 1, 6)),
+                3,
+            )
+            .unwrap();
+        assert_eq!(repo.len(), 4);
+        assert_eq!(repo.warm_up(), &history[..2]);
+        assert_eq!(repo.net_asset_value_history(), &history[2..6]);
+        assert_eq!(
+            repo.check().unwrap(),
+            (NaiveDate::from_ymd(2021, 1, 3), 3.0)
+        );
+        assert_eq!(repo.window(2), &history[1..3]);
+        assert!(repo.invest(100.0).is_ok());
+        assert_eq!(repo.window(10), &history[..4]);
+        while repo.pass().is_ok() {}
+        assert_eq!(repo.window(1), &history[5..6]);
+        assert!(
+            if let Err(Error::Insufficient) = Repository::new(Box::new(|_| 0.0), history)
+                .unwrap()
+                .with_range(Some(NaiveDate::from_ymd(2021, 2, 1)), None, 0)
+            {
+                true
+            } else {
+                false
+            }
+        );
+    }
+
+    #[test]
+    fn test_undo() {
+        let repository = || {
+            Repository::new(
+                Box::new(Fifo::new(vec![], vec![(2, 0.1)])),
+                NaiveDate::from_ymd(2021, 1, 1)
+                    .iter_days()
+                    .take(3)
+                    .map(|date| (date, 1.0))
+                    .collect(),
+            )
+            .unwrap()
+        };
+        let mut repo = repository().with_undo(2);
+        assert!(if let Err(Error::Insufficient) = repo.undo() {
+            true
+        } else {
+            false
+        });
+        assert!(repo.invest(100.0).is_ok());
+        assert_eq!(repo.redemption_fee(100.0).unwrap(), 10.0);
+        assert_eq!(repo.redemption_fee(100.0).unwrap(), 10.0);
+        assert!(repo.redeem(50.0).is_ok());
+        let infos = repo.daily_infos().to_vec();
+        assert!(repo.undo().is_ok());
+        assert_eq!(repo.transactions().len(), 1);
+        assert_eq!(repo.redemption_fee(100.0).unwrap(), 10.0);
+        assert!(repo.redeem(50.0).is_ok());
+        assert_eq!(repo.daily_infos(), &infos[..]);
+        assert!(repo.undo().is_ok());
+        assert!(repo.undo().is_ok());
+        assert_eq!(repo.redemption_fee(100.0).unwrap(), 0.0);
+        assert!(repo.transactions().is_empty());
+
+        let mut repo = repository().with_undo(1);
+        assert!(repo.invest(100.0).is_ok());
+        assert!(repo.pass().is_ok());
+        assert!(repo.undo().is_ok());
+        assert!(if let Err(Error::Insufficient) = repo.undo() {
+            true
+        } else {
+            false
+        });
+        assert!(repo.restart().is_ok());
+        assert!(repo.daily_infos().is_empty());
+        assert!(repo.transactions().is_empty());
+        assert_eq!(repo.redemption_fee(100.0).unwrap(), 0.0);
+
+        // a closure is copied as it is
+        let mut repo = Repository::new(
+            Box::new(|_| 0.0),
+            vec![
+                (NaiveDate::from_ymd(2021, 1, 1), 1.0),
+                (NaiveDate::from_ymd(2021, 1, 2), 1.0),
+            ],
+        )
+        .unwrap()
+        .with_undo(1);
+        assert!(repo.invest(100.0).is_ok());
+        assert!(repo.undo().is_ok());
+        assert!(repo.invest(100.0).is_ok());
+        assert!(repo.restart().is_ok());
+        assert!(repo.transactions().is_empty());
+
+        let mut repo = repository();
+        assert!(repo.invest(100.0).is_ok());
+        assert!(if let Err(Error::Insufficient) = repo.undo() {
+            true
+        } else {
+            false
+        });
+        assert!(if let Err(Error::Invalid) = repo.restart() {
+            true
+        } else {
+            false
+        });
+    }
 }
@@ -0,0 +1,210 @@
+use crate::{
+    config::Config,
+    error::{Error, Result},
+    metrics::{Distribution, Metric, Metrics},
+};
+use chrono::{Datelike, NaiveDate};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Starts the strategy repeatedly for a fixed horizon in trading days, e.g.
+///
+/// ```toml
+/// [rolling]
+/// horizon = 250
+/// every = 20
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RollingConfig {
+    pub horizon: usize,
+    /// Trading days between two starts, the first trading day of every month
+    /// if absent.
+    pub every: Option<usize>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Run {
+    pub start: NaiveDate,
+    pub end: NaiveDate,
+    pub metrics: Metrics,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Summary {
+    pub distribution: Distribution,
+    pub worst: Option<(NaiveDate, f64)>,
+    pub best: Option<(NaiveDate, f64)>,
+}
+
+impl Summary {
+    fn new(runs: &[Run], metric: Metric) -> Self {
+        let values: Vec<(NaiveDate, f64)> = runs
+            .iter()
+            .map(|run| (run.start, run.metrics.get(metric)))
+            .filter(|(_, value)| !value.is_nan())
+            .collect();
+        let better = |a: f64, b: f64| {
+            if metric.is_ascending() {
+                a > b
+            } else {
+                a < b
+            }
+        };
+        let best = values.iter().fold(None, |best, &(date, value)| match best {
+            Some((_, best_value)) if !better(value, best_value) => best,
+            _ => Some((date, value)),
+        });
+        let worst = values
+            .iter()
+            .fold(None, |worst, &(date, value)| match worst {
+                Some((_, worst_value)) if !better(worst_value, value) => worst,
+                _ => Some((date, value)),
+            });
+        Self {
+            distribution: Distribution::new(
+                &values.iter().map(|&(_, value)| value).collect::<Vec<_>>(),
+            ),
+            worst,
+            best,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Rolling {
+    pub runs: Vec<Run>,
+    pub total_return: Summary,
+    pub xirr: Summary,
+    pub max_drawdown: Summary,
+    pub probability_of_loss: f64,
+}
+
+impl Rolling {
+    pub fn new(
+        config: &Config,
+        rolling: &RollingConfig,
+        history: &[(NaiveDate, f64)],
+    ) -> Result<Self> {
+        if rolling.horizon == 0 || rolling.every == Some(0) {
+            return Err(Error::Invalid);
+        }
+        let dates: Vec<NaiveDate> = config
+            .repository(history.to_vec())?
+            .net_asset_value_history()
+            .iter()
+            .map(|&(date, _)| date)
+            .collect();
+        let last = (dates.len() + 1).saturating_sub(rolling.horizon);
+        let starts: Vec<usize> = match rolling.every {
+            Some(every) => (0..last).step_by(every).collect(),
+            None => (0..last)
+                .filter(|&i| {
+                    i == 0
+                        || (dates[i].year(), dates[i].month())
+                            != (dates[i - 1].year(), dates[i - 1].month())
+                })
+                .collect(),
+        };
+        let runs = starts
+            .into_par_iter()
+            .map(|i| {
+                let (start, end) = (dates[i], dates[i + rolling.horizon - 1]);
+                let mut config = config.clone();
+                config.data.start = Some(start);
+                config.data.end = Some(end);
+                Ok(Run {
+                    start,
+                    end,
+                    metrics: Metrics::new(&config.run(history.to_vec())?),
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self {
+            total_return: Summary::new(&runs, Metric::TotalReturn),
+            xirr: Summary::new(&runs, Metric::Xirr),
+            max_drawdown: Summary::new(&runs, Metric::MaxDrawdown),
+            probability_of_loss: runs.iter().filter(|run| run.metrics.profit < 0.).count() as f64
+                / runs.len().max(1) as f64,
+            runs,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rolling(every: Option<usize>) -> Result<Rolling> {
+        let config: Config = toml::from_str(
+            r#"
+            [data]
+            path = "a.csv"
+
+            [strategy]
+            type = "dca"
+            amount = 100.0
+            interval = 1
+            "#,
+        )
+        .unwrap();
+        let history: Vec<(NaiveDate, f64)> = NaiveDate::from_ymd(2021, 1, 28)
+            .iter_days()
+            .zip(vec![1.0, 1.0, 2.0, 2.0, 2.0, 1.0, 1.0, 1.0, 1.0])
+            .collect();
+        Rolling::new(&config, &RollingConfig { horizon: 3, every }, &history)
+    }
+
+    #[test]
+    fn test_rolling() {
+        let monthly = rolling(None).unwrap();
+        let starts: Vec<(NaiveDate, NaiveDate)> = monthly
+            .runs
+            .iter()
+            .map(|run| (run.start, run.end))
+            .collect();
+        assert_eq!(
+            starts,
+            vec![
+                (
+                    NaiveDate::from_ymd(2021, 1, 28),
+                    NaiveDate::from_ymd(2021, 1, 30)
+                ),
+                (
+                    NaiveDate::from_ymd(2021, 2, 1),
+                    NaiveDate::from_ymd(2021, 2, 3)
+                ),
+            ]
+        );
+        // 250 shares bought for 300 on 1, 1 and 2, then on 2, 1 and 1.
+        assert_eq!(monthly.runs[0].metrics.investment, 300.0);
+        assert_eq!(monthly.runs[0].metrics.profit, 200.0);
+        assert_eq!(monthly.runs[1].metrics.profit, -50.0);
+        assert_eq!(
+            monthly.total_return.best,
+            Some((NaiveDate::from_ymd(2021, 1, 28), 200.0 / 300.0))
+        );
+        assert_eq!(
+            monthly.total_return.worst,
+            Some((NaiveDate::from_ymd(2021, 2, 1), -50.0 / 300.0))
+        );
+        assert_eq!(monthly.probability_of_loss, 0.5);
+
+        let every = rolling(Some(2)).unwrap();
+        let starts: Vec<NaiveDate> = every.runs.iter().map(|run| run.start).collect();
+        assert_eq!(
+            starts,
+            vec![
+                NaiveDate::from_ymd(2021, 1, 28),
+                NaiveDate::from_ymd(2021, 1, 30),
+                NaiveDate::from_ymd(2021, 2, 1),
+                NaiveDate::from_ymd(2021, 2, 3),
+            ]
+        );
+        assert_eq!(every.runs[3].end, NaiveDate::from_ymd(2021, 2, 5));
+        assert!(if let Err(Error::Invalid) = rolling(Some(0)) {
+            true
+        } else {
+            false
+        });
+    }
+}
@@ -0,0 +1,60 @@
+use super::lots::{Lots, Priority};
+use crate::repository::{Order, Rule};
+
+/// Redeems the lots with the lowest redemption rate first, e.g. those past the
+/// penalty-free holding period, and the oldest among equals.
+#[derive(Clone)]
+pub struct CheapestFirst {
+    lots: Lots,
+}
+
+impl CheapestFirst {
+    pub fn new(investment_rates: Vec<(f64, f64)>, redemption_rates: Vec<(usize, f64)>) -> Self {
+        Self {
+            lots: Lots::new(investment_rates, redemption_rates),
+        }
+    }
+}
+
+impl Rule for CheapestFirst {
+    fn fee(&mut self, order: Order) -> f64 {
+        self.lots.fee(order, Priority::Cheapest)
+    }
+
+    fn accrue(&mut self, ratio: f64) {
+        self.lots.scale(ratio);
+    }
+
+    fn snapshot(&self) -> Option<Box<dyn Rule>> {
+        Some(Box::new(self.clone()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    #[test]
+    fn test_cheapest_first() {
+        // a rate that rises after a year, as some bond funds charge
+        let mut rule = CheapestFirst::new(vec![], vec![(7, 0.015), (365, 0.0), (730, 0.005)]);
+        for &(year, month) in &[(2020, 1), (2021, 3), (2021, 6)] {
+            rule.fee(Order::Investment {
+                date: NaiveDate::from_ymd(year, month, 1),
+                net_asset_value: 1.0,
+                investment: 100.0,
+            });
+        }
+        let redemption = |rule: &mut CheapestFirst, redemption| {
+            rule.fee(Order::Redemption {
+                date: NaiveDate::from_ymd(2021, 6, 3),
+                net_asset_value: 1.0,
+                redemption,
+            })
+        };
+        assert_eq!(redemption(&mut rule, 100.0), 0.0);
+        assert!((redemption(&mut rule, 100.0) - 100.0 * 0.005).abs() < 1e-9);
+        assert!((redemption(&mut rule, 50.0) - 50.0 * 0.015).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,55 @@
+use crate::repository::{Order, Rule};
+use chrono::NaiveDate;
+
+/// Keeps the fee of every order of another rule within `[minimum, maximum]`.
+#[derive(Debug)]
+pub struct Clamp {
+    rule: Box<dyn Rule>,
+    minimum: f64,
+    maximum: f64,
+}
+
+impl Clamp {
+    pub fn new(rule: Box<dyn Rule>) -> Self {
+        Self {
+            rule,
+            minimum: 0.,
+            maximum: f64::INFINITY,
+        }
+    }
+
+    pub fn with_minimum(mut self, minimum: f64) -> Self {
+        self.minimum = minimum;
+        self
+    }
+
+    pub fn with_maximum(mut self, maximum: f64) -> Self {
+        self.maximum = maximum;
+        self
+    }
+}
+
+impl Rule for Clamp {
+    fn fee(&mut self, order: Order) -> f64 {
+        self.rule.fee(order).max(self.minimum).min(self.maximum)
+    }
+
+    fn accrual(&mut self, date: NaiveDate, days: i64, value: f64) -> f64 {
+        self.rule.accrual(date, days, value)
+    }
+
+    fn accrue(&mut self, ratio: f64) {
+        self.rule.accrue(ratio);
+    }
+
+    fn snapshot(&self) -> Option<Box<dyn Rule>> {
+        Some(Box::new(Clamp {
+            rule: self.rule.snapshot()?,
+            ..*self
+        }))
+    }
+
+    fn quote(&self, order: Order) -> Option<f64> {
+        Some(self.rule.quote(order)?.max(self.minimum).min(self.maximum))
+    }
+}
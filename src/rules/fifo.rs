@@ -1,92 +1,38 @@
+use super::lots::{Lots, Priority};
 use crate::repository::{Order, Rule};
-use chrono::NaiveDate;
-use std::collections::VecDeque;
 
+/// Redeems the oldest lots first.
+#[derive(Clone)]
 pub struct Fifo {
-    queue: VecDeque<(NaiveDate, f64)>, // (invest_date, share)
-    investment_rates: Vec<(f64, f64)>,
-    redemption_rates: Vec<(usize, f64)>,
+    lots: Lots,
 }
 
 impl Fifo {
     pub fn new(investment_rates: Vec<(f64, f64)>, redemption_rates: Vec<(usize, f64)>) -> Self {
         Self {
-            queue: VecDeque::new(),
-            investment_rates,
-            redemption_rates,
+            lots: Lots::new(investment_rates, redemption_rates),
         }
     }
 }
 
 impl Rule for Fifo {
     fn fee(&mut self, order: Order) -> f64 {
-        match order {
-            Order::Investment {
-                date,
-                net_asset_value,
-                investment,
-            } => {
-                let fee = self
-                    .investment_rates
-                    .iter()
-                    .find(|&&(bound, _)| investment < bound)
-                    .map_or(0., |&(_, rate)| rate * investment);
-                self.queue
-                    .push_back((date, (investment - fee) / net_asset_value));
-                fee
-            }
-            Order::Redemption {
-                date,
-                net_asset_value,
-                mut redemption,
-            } => {
-                let mut fee = 0.;
-                while let Some((invest_date, share)) = self.queue.pop_front() {
-                    if redemption < share {
-                        self.queue.push_front((invest_date, share - redemption));
-                        fee += calculate_redemption_fee(
-                            &self.redemption_rates,
-                            invest_date,
-                            date,
-                            net_asset_value,
-                            redemption,
-                        );
-                        break;
-                    } else {
-                        redemption -= share;
-                        fee += calculate_redemption_fee(
-                            &self.redemption_rates,
-                            invest_date,
-                            date,
-                            net_asset_value,
-                            share,
-                        );
-                    }
-                }
-                fee
-            }
-        }
+        self.lots.fee(order, Priority::Oldest)
     }
-}
 
-fn calculate_redemption_fee(
-    redemption_rates: &[(usize, f64)],
-    invest_date: NaiveDate,
-    date: NaiveDate,
-    net_asset_value: f64,
-    share: f64,
-) -> f64 {
-    share
-        * net_asset_value
-        * redemption_rates
-            .into_iter()
-            .find(|&&(bound, _)| (date - invest_date).num_days() < bound as i64)
-            .map_or(0., |&(_, rate)| rate)
+    fn accrue(&mut self, ratio: f64) {
+        self.lots.scale(ratio);
+    }
+
+    fn snapshot(&self) -> Option<Box<dyn Rule>> {
+        Some(Box::new(self.clone()))
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use chrono::NaiveDate;
 
     #[test]
     fn test_7_30() {
@@ -0,0 +1,57 @@
+use super::lots::{Lots, Priority};
+use crate::repository::{Order, Rule};
+
+/// Redeems the newest lots first.
+#[derive(Clone)]
+pub struct Lifo {
+    lots: Lots,
+}
+
+impl Lifo {
+    pub fn new(investment_rates: Vec<(f64, f64)>, redemption_rates: Vec<(usize, f64)>) -> Self {
+        Self {
+            lots: Lots::new(investment_rates, redemption_rates),
+        }
+    }
+}
+
+impl Rule for Lifo {
+    fn fee(&mut self, order: Order) -> f64 {
+        self.lots.fee(order, Priority::Newest)
+    }
+
+    fn accrue(&mut self, ratio: f64) {
+        self.lots.scale(ratio);
+    }
+
+    fn snapshot(&self) -> Option<Box<dyn Rule>> {
+        Some(Box::new(self.clone()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    #[test]
+    fn test_newest_first() {
+        let mut rule = Lifo::new(vec![], vec![(7, 0.015), (30, 0.005)]);
+        for &day in &[1, 20] {
+            rule.fee(Order::Investment {
+                date: NaiveDate::from_ymd(2021, 1, day),
+                net_asset_value: 1.0,
+                investment: 100.0,
+            });
+        }
+        assert!(
+            (rule.fee(Order::Redemption {
+                date: NaiveDate::from_ymd(2021, 1, 25),
+                net_asset_value: 1.0,
+                redemption: 150.0,
+            }) - (100.0 * 0.015 + 50.0 * 0.005))
+                .abs()
+                < 1e-9
+        );
+    }
+}
@@ -0,0 +1,134 @@
+use crate::repository::Order;
+use chrono::NaiveDate;
+use std::{collections::VecDeque, convert::TryFrom};
+
+/// Which lot a redemption consumes first.
+#[derive(Clone, Copy)]
+pub(super) enum Priority {
+    Oldest,
+    Newest,
+    /// The lot with the lowest redemption rate, the oldest among equals.
+    Cheapest,
+}
+
+/// The lots of a holding, charged by investment amount and holding days.
+#[derive(Clone)]
+pub(super) struct Lots {
+    queue: VecDeque<(NaiveDate, f64)>, // (invest_date, share)
+    investment_rates: Vec<(f64, f64)>,
+    redemption_rates: Vec<(usize, f64)>,
+}
+
+impl Lots {
+    pub(super) fn new(
+        investment_rates: Vec<(f64, f64)>,
+        redemption_rates: Vec<(usize, f64)>,
+    ) -> Self {
+        Self {
+            queue: VecDeque::new(),
+            investment_rates,
+            redemption_rates,
+        }
+    }
+
+    pub(super) fn fee(&mut self, order: Order, priority: Priority) -> f64 {
+        match order {
+            Order::Investment {
+                date,
+                net_asset_value,
+                investment,
+            } => {
+                let fee = self
+                    .investment_rates
+                    .iter()
+                    .find(|&&(bound, _)| investment < bound)
+                    .map_or(0., |&(_, rate)| rate * investment);
+                self.queue
+                    .push_back((date, (investment - fee) / net_asset_value));
+                fee
+            }
+            Order::Redemption {
+                date,
+                net_asset_value,
+                mut redemption,
+            }
+            | Order::SwitchOut {
+                date,
+                net_asset_value,
+                mut redemption,
+            } => {
+                let mut fee = 0.;
+                while let Some(i) = self.next(priority, date) {
+                    let (invest_date, share) = self.queue[i];
+                    if redemption < share {
+                        self.queue[i].1 = share - redemption;
+                        fee += calculate_redemption_fee(
+                            &self.redemption_rates,
+                            invest_date,
+                            date,
+                            net_asset_value,
+                            redemption,
+                        );
+                        break;
+                    } else {
+                        self.queue.remove(i);
+                        redemption -= share;
+                        fee += calculate_redemption_fee(
+                            &self.redemption_rates,
+                            invest_date,
+                            date,
+                            net_asset_value,
+                            share,
+                        );
+                    }
+                }
+                fee
+            }
+            Order::SwitchIn { date, share, .. } => {
+                self.queue.push_back((date, share));
+                0.
+            }
+        }
+    }
+
+    pub(super) fn scale(&mut self, ratio: f64) {
+        for (_, share) in &mut self.queue {
+            *share *= ratio;
+        }
+    }
+
+    /// The index of the lot to redeem from on `date`.
+    fn next(&self, priority: Priority, date: NaiveDate) -> Option<usize> {
+        if self.queue.is_empty() {
+            return None;
+        }
+        Some(match priority {
+            Priority::Oldest => 0,
+            Priority::Newest => self.queue.len() - 1,
+            Priority::Cheapest => {
+                let rate = |i: usize| {
+                    calculate_redemption_fee(&self.redemption_rates, self.queue[i].0, date, 1., 1.)
+                };
+                (1..self.queue.len()).fold(0, |best, i| if rate(i) < rate(best) { i } else { best })
+            }
+        })
+    }
+}
+
+fn calculate_redemption_fee(
+    redemption_rates: &[(usize, f64)],
+    invest_date: NaiveDate,
+    date: NaiveDate,
+    net_asset_value: f64,
+    share: f64,
+) -> f64 {
+    share
+        * net_asset_value
+        * redemption_rates
+            .iter()
+            // `usize::MAX` for the open-ended tier
+            .find(|&&(bound, _)| {
+                (date - invest_date).num_days() < i64::try_from(bound).unwrap_or(i64::MAX)
+            })
+            .map_or(0., |&(_, rate)| rate)
+}
@@ -1,3 +1,22 @@
+pub use cheapest_first::CheapestFirst;
+pub use clamp::Clamp;
 pub use fifo::Fifo;
+pub use lifo::Lifo;
+pub use recurring::Recurring;
+pub use split::Split;
+pub use sum::Sum;
+pub use surcharge::Surcharge;
+pub use switch::Switch;
+pub use tiered::Tiered;
 
+mod cheapest_first;
+mod clamp;
 mod fifo;
+mod lifo;
+mod lots;
+mod recurring;
+mod split;
+mod sum;
+mod surcharge;
+mod switch;
+mod tiered;
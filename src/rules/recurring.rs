@@ -0,0 +1,134 @@
+use crate::repository::{Order, Rule};
+use chrono::NaiveDate;
+
+/// Adds annual management, custody and sales-service fees, accrued daily on
+/// the holding value, to another rule.
+#[derive(Debug)]
+pub struct Recurring {
+    rule: Box<dyn Rule>,
+    management: f64,
+    custody: f64,
+    sales_service: f64,
+}
+
+impl Recurring {
+    pub fn new(rule: Box<dyn Rule>) -> Self {
+        Self {
+            rule,
+            management: 0.,
+            custody: 0.,
+            sales_service: 0.,
+        }
+    }
+
+    pub fn with_management(mut self, rate: f64) -> Self {
+        self.management = rate;
+        self
+    }
+
+    pub fn with_custody(mut self, rate: f64) -> Self {
+        self.custody = rate;
+        self
+    }
+
+    pub fn with_sales_service(mut self, rate: f64) -> Self {
+        self.sales_service = rate;
+        self
+    }
+}
+
+impl Rule for Recurring {
+    fn fee(&mut self, order: Order) -> f64 {
+        self.rule.fee(order)
+    }
+
+    fn accrual(&mut self, date: NaiveDate, days: i64, value: f64) -> f64 {
+        self.rule.accrual(date, days, value)
+            + value * (self.management + self.custody + self.sales_service) * days as f64 / 365.
+    }
+
+    fn accrue(&mut self, ratio: f64) {
+        self.rule.accrue(ratio);
+    }
+
+    fn snapshot(&self) -> Option<Box<dyn Rule>> {
+        Some(Box::new(Recurring {
+            rule: self.rule.snapshot()?,
+            ..*self
+        }))
+    }
+
+    fn quote(&self, order: Order) -> Option<f64> {
+        self.rule.quote(order)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        repository::{Repository, Transaction},
+        rules::Fifo,
+    };
+
+    #[test]
+    fn test_accrual() {
+        let rule = Recurring::new(Box::new(|_| 0.0))
+            .with_management(0.012)
+            .with_custody(0.002)
+            .with_sales_service(0.0045);
+        let mut repo = Repository::new(
+            Box::new(rule),
+            vec![
+                (NaiveDate::from_ymd(2021, 1, 1), 1.0),
+                (NaiveDate::from_ymd(2021, 1, 4), 1.0),
+                (NaiveDate::from_ymd(2021, 1, 5), 1.0),
+            ],
+        )
+        .unwrap();
+        assert!(repo.invest(36500.0).is_ok());
+        let info = repo.daily_infos().last().unwrap().clone();
+        assert!((info.cumulative_accrued_fee() - 3. * 1.85).abs() < 1e-9);
+        assert!((info.holding_share() - (36500. - 3. * 1.85)).abs() < 1e-9);
+        assert!(repo.pass().is_ok());
+        let fee = info.holding_share() * 0.0185 / 365.;
+        let info = repo.daily_infos().last().unwrap();
+        assert!((info.cumulative_accrued_fee() - 3. * 1.85 - fee).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_redeem_all() {
+        let mut repo = Repository::new(
+            Box::new(
+                Recurring::new(Box::new(Fifo::new(vec![], vec![(7, 0.015)])))
+                    .with_management(0.365),
+            ),
+            NaiveDate::from_ymd(2021, 1, 1)
+                .iter_days()
+                .take(5)
+                .map(|date| (date, 1.0))
+                .collect(),
+        )
+        .unwrap();
+        assert!(repo.invest(1000.0).is_ok());
+        assert!(repo.pass().is_ok());
+        assert!(repo.pass().is_ok());
+        let share = repo.daily_infos().last().unwrap().holding_share();
+        assert!((share - 1000.0 * 0.999f64.powi(3)).abs() < 1e-9);
+        assert!(repo.redeem(share).is_ok());
+        assert!(match repo.transactions()[1] {
+            Transaction::Redemption { fee, .. } => (fee - share * 0.015).abs() < 1e-9,
+            _ => false,
+        });
+        // no lot is left behind
+        let next = repo.check().unwrap().0;
+        assert_eq!(
+            repo.rule().quote(Order::Redemption {
+                date: next,
+                net_asset_value: 1.0,
+                redemption: 1.0,
+            }),
+            Some(0.0)
+        );
+    }
+}
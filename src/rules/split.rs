@@ -0,0 +1,106 @@
+use crate::repository::{Order, Rule};
+use chrono::NaiveDate;
+
+/// Charges investments and incoming switches by one rule, and redemptions and
+/// outgoing switches by another.
+///
+/// Both rules see every order so that they can keep their state, e.g. the
+/// lots of a `Fifo`. The redemption rule sees investments net of the
+/// investment fee, so a `Fifo` without investment rates records the shares
+/// actually bought.
+#[derive(Debug)]
+pub struct Split {
+    investment: Box<dyn Rule>,
+    redemption: Box<dyn Rule>,
+}
+
+impl Split {
+    pub fn new(investment: Box<dyn Rule>, redemption: Box<dyn Rule>) -> Self {
+        Self {
+            investment,
+            redemption,
+        }
+    }
+}
+
+impl Rule for Split {
+    fn fee(&mut self, order: Order) -> f64 {
+        match order {
+            Order::Investment {
+                date,
+                net_asset_value,
+                investment,
+            } => {
+                let fee = self.investment.fee(order);
+                self.redemption.fee(Order::Investment {
+                    date,
+                    net_asset_value,
+                    investment: investment - fee,
+                });
+                fee
+            }
+            Order::SwitchIn { .. } => {
+                self.redemption.fee(order);
+                self.investment.fee(order)
+            }
+            Order::Redemption { .. } | Order::SwitchOut { .. } => {
+                self.investment.fee(order);
+                self.redemption.fee(order)
+            }
+        }
+    }
+
+    fn accrual(&mut self, date: NaiveDate, days: i64, value: f64) -> f64 {
+        self.investment.accrual(date, days, value) + self.redemption.accrual(date, days, value)
+    }
+
+    fn accrue(&mut self, ratio: f64) {
+        self.investment.accrue(ratio);
+        self.redemption.accrue(ratio);
+    }
+
+    fn snapshot(&self) -> Option<Box<dyn Rule>> {
+        Some(Box::new(Split {
+            investment: self.investment.snapshot()?,
+            redemption: self.redemption.snapshot()?,
+        }))
+    }
+
+    fn quote(&self, order: Order) -> Option<f64> {
+        match order {
+            Order::Investment { .. } | Order::SwitchIn { .. } => self.investment.quote(order),
+            Order::Redemption { .. } | Order::SwitchOut { .. } => self.redemption.quote(order),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::{Fifo, Tiered};
+
+    #[test]
+    fn test_tiered_fifo() {
+        let mut rule = Split::new(
+            Box::new(Tiered::new(vec![(1_000_000.0, 0.015)], Some(1000.0))),
+            Box::new(Fifo::new(vec![], vec![(7, 0.015), (30, 0.005)])),
+        );
+        assert_eq!(
+            rule.fee(Order::Investment {
+                date: NaiveDate::from_ymd(2021, 1, 1),
+                net_asset_value: 1.0,
+                investment: 10_000.0,
+            }),
+            147.78
+        );
+        assert!(
+            (rule.fee(Order::Redemption {
+                date: NaiveDate::from_ymd(2021, 1, 11),
+                net_asset_value: 1.0,
+                redemption: 10_000.0,
+            }) - 9852.22 * 0.005)
+                .abs()
+                < 1e-9
+        );
+    }
+}
@@ -0,0 +1,47 @@
+use crate::repository::{Order, Rule};
+use chrono::NaiveDate;
+
+/// Charges the total fee of several rules.
+#[derive(Debug)]
+pub struct Sum {
+    rules: Vec<Box<dyn Rule>>,
+}
+
+impl Sum {
+    pub fn new(rules: Vec<Box<dyn Rule>>) -> Self {
+        Self { rules }
+    }
+}
+
+impl Rule for Sum {
+    fn fee(&mut self, order: Order) -> f64 {
+        self.rules.iter_mut().map(|rule| rule.fee(order)).sum()
+    }
+
+    fn accrual(&mut self, date: NaiveDate, days: i64, value: f64) -> f64 {
+        self.rules
+            .iter_mut()
+            .map(|rule| rule.accrual(date, days, value))
+            .sum()
+    }
+
+    fn accrue(&mut self, ratio: f64) {
+        for rule in &mut self.rules {
+            rule.accrue(ratio);
+        }
+    }
+
+    fn snapshot(&self) -> Option<Box<dyn Rule>> {
+        Some(Box::new(Sum {
+            rules: self
+                .rules
+                .iter()
+                .map(|rule| rule.snapshot())
+                .collect::<Option<_>>()?,
+        }))
+    }
+
+    fn quote(&self, order: Order) -> Option<f64> {
+        self.rules.iter().map(|rule| rule.quote(order)).sum()
+    }
+}
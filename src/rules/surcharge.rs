@@ -0,0 +1,40 @@
+use crate::repository::{Order, Rule};
+use chrono::NaiveDate;
+
+/// Adds a fixed platform charge to every order of another rule.
+#[derive(Debug)]
+pub struct Surcharge {
+    rule: Box<dyn Rule>,
+    charge: f64,
+}
+
+impl Surcharge {
+    pub fn new(rule: Box<dyn Rule>, charge: f64) -> Self {
+        Self { rule, charge }
+    }
+}
+
+impl Rule for Surcharge {
+    fn fee(&mut self, order: Order) -> f64 {
+        self.rule.fee(order) + self.charge
+    }
+
+    fn accrual(&mut self, date: NaiveDate, days: i64, value: f64) -> f64 {
+        self.rule.accrual(date, days, value)
+    }
+
+    fn accrue(&mut self, ratio: f64) {
+        self.rule.accrue(ratio);
+    }
+
+    fn snapshot(&self) -> Option<Box<dyn Rule>> {
+        Some(Box::new(Surcharge {
+            rule: self.rule.snapshot()?,
+            charge: self.charge,
+        }))
+    }
+
+    fn quote(&self, order: Order) -> Option<f64> {
+        Some(self.rule.quote(order)? + self.charge)
+    }
+}
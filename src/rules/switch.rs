@@ -0,0 +1,100 @@
+use crate::repository::{Order, Rule};
+use chrono::NaiveDate;
+
+/// Charges orders before `date` by one rule and the others by another, for fee
+/// schedules that change during the history.
+///
+/// Both rules see every order so that they can keep their state.
+#[derive(Debug)]
+pub struct Switch {
+    before: Box<dyn Rule>,
+    after: Box<dyn Rule>,
+    date: NaiveDate,
+}
+
+impl Switch {
+    pub fn new(before: Box<dyn Rule>, after: Box<dyn Rule>, date: NaiveDate) -> Self {
+        Self {
+            before,
+            after,
+            date,
+        }
+    }
+}
+
+impl Rule for Switch {
+    fn fee(&mut self, order: Order) -> f64 {
+        let date = order.date();
+        let before = self.before.fee(order);
+        let after = self.after.fee(order);
+        if date < self.date {
+            before
+        } else {
+            after
+        }
+    }
+
+    fn accrual(&mut self, date: NaiveDate, days: i64, value: f64) -> f64 {
+        if date < self.date {
+            self.before.accrual(date, days, value)
+        } else {
+            self.after.accrual(date, days, value)
+        }
+    }
+
+    fn accrue(&mut self, ratio: f64) {
+        self.before.accrue(ratio);
+        self.after.accrue(ratio);
+    }
+
+    fn snapshot(&self) -> Option<Box<dyn Rule>> {
+        Some(Box::new(Switch {
+            before: self.before.snapshot()?,
+            after: self.after.snapshot()?,
+            date: self.date,
+        }))
+    }
+
+    fn quote(&self, order: Order) -> Option<f64> {
+        if order.date() < self.date {
+            self.before.quote(order)
+        } else {
+            self.after.quote(order)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::{Clamp, Fifo, Sum, Surcharge};
+
+    #[test]
+    fn test_switch() {
+        let mut rule = Sum::new(vec![
+            Box::new(Switch::new(
+                Box::new(Fifo::new(vec![(f64::INFINITY, 0.015)], vec![(7, 0.015)])),
+                Box::new(Fifo::new(vec![(f64::INFINITY, 0.001)], vec![(7, 0.015)])),
+                NaiveDate::from_ymd(2021, 1, 5),
+            )),
+            Box::new(
+                Clamp::new(Box::new(Surcharge::new(Box::new(|_| 0.0), 2.0))).with_maximum(1.0),
+            ),
+        ]);
+        let investment = |day| Order::Investment {
+            date: NaiveDate::from_ymd(2021, 1, day),
+            net_asset_value: 1.0,
+            investment: 1000.0,
+        };
+        assert_eq!(rule.fee(investment(4)), 16.0);
+        assert_eq!(rule.fee(investment(5)), 2.0);
+        assert_eq!(
+            rule.fee(Order::Redemption {
+                date: NaiveDate::from_ymd(2021, 1, 6),
+                net_asset_value: 1.0,
+                redemption: 100.0,
+            }),
+            2.5
+        );
+    }
+}
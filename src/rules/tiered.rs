@@ -0,0 +1,88 @@
+use crate::repository::{Order, Rule};
+
+/// A front-end investment fee charged on the net amount (价外法):
+/// `investment - investment / (1 + rate)`, rounded to the cent.
+///
+/// The rate comes from the first tier whose bound exceeds the investment and
+/// is multiplied by the platform discount, e.g. `0.1` for 1折. Investments
+/// beyond every bound pay the undiscounted fixed fee, if any. Other orders are
+/// free.
+#[derive(Clone)]
+pub struct Tiered {
+    rates: Vec<(f64, f64)>,
+    fixed: Option<f64>,
+    discount: f64,
+    minimum: f64,
+}
+
+impl Tiered {
+    pub fn new(rates: Vec<(f64, f64)>, fixed: Option<f64>) -> Self {
+        Self {
+            rates,
+            fixed,
+            discount: 1.,
+            minimum: 0.,
+        }
+    }
+
+    pub fn with_discount(mut self, discount: f64) -> Self {
+        self.discount = discount;
+        self
+    }
+
+    /// The least rate-based fee, unless the discounted rate is zero.
+    pub fn with_minimum(mut self, minimum: f64) -> Self {
+        self.minimum = minimum;
+        self
+    }
+
+    pub fn investment_fee(&self, investment: f64) -> f64 {
+        match self.rates.iter().find(|&&(bound, _)| investment < bound) {
+            Some(&(_, rate)) if rate * self.discount > 0. => {
+                let fee = investment - investment / (1. + rate * self.discount);
+                round(fee.max(self.minimum).min(investment))
+            }
+            Some(_) => 0.,
+            None => self.fixed.map_or(0., |fee| fee.min(investment)),
+        }
+    }
+}
+
+impl Rule for Tiered {
+    fn fee(&mut self, order: Order) -> f64 {
+        match order {
+            Order::Investment { investment, .. } => self.investment_fee(investment),
+            _ => 0.,
+        }
+    }
+
+    fn snapshot(&self) -> Option<Box<dyn Rule>> {
+        Some(Box::new(self.clone()))
+    }
+}
+
+fn round(money: f64) -> f64 {
+    (money * 100.).round() / 100.
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prospectus() {
+        let rule = Tiered::new(
+            vec![(1_000_000.0, 0.015), (5_000_000.0, 0.012)],
+            Some(1000.0),
+        );
+        assert_eq!(rule.investment_fee(10_000.0), 147.78);
+        assert_eq!(rule.investment_fee(2_000_000.0), 23_715.42);
+        assert_eq!(rule.investment_fee(5_000_000.0), 1000.0);
+        let rule = rule.with_discount(0.1);
+        assert_eq!(rule.investment_fee(10_000.0), 14.98);
+        assert_eq!(rule.investment_fee(6_000_000.0), 1000.0);
+        let rule = rule.with_minimum(0.1);
+        assert_eq!(rule.investment_fee(10.0), 0.1);
+        assert_eq!(rule.investment_fee(0.05), 0.05);
+    }
+}
@@ -0,0 +1,230 @@
+use crate::{
+    config::LotOrder,
+    error::{Error, Result},
+    repository::Rule,
+    rules::{Recurring, Split, Switch, Tiered},
+};
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use std::{fs, path::Path};
+
+/// A fee schedule as published in a prospectus, e.g.
+///
+/// ```toml
+/// [[periods]]
+/// investment = [
+///     { below = 1000000.0, rate = 0.015 },
+///     { below = 5000000.0, rate = 0.012 },
+///     { fixed = 1000.0 },
+/// ]
+/// redemption = [{ below_days = 7, rate = 0.015 }, { below_days = 365, rate = 0.005 }]
+/// discount = 0.1
+/// management = 0.012
+///
+/// [[periods]]
+/// effective = "2021-06-01"
+/// investment = [{ rate = 0.012 }]
+/// redemption = [{ below_days = 7, rate = 0.015 }]
+/// ```
+///
+/// Each period applies from its `effective` date until the next one. Holdings
+/// older than every redemption tier are redeemed for free.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Schedule {
+    pub periods: Vec<Period>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Period {
+    /// Only optional for the first period.
+    pub effective: Option<NaiveDate>,
+    #[serde(default)]
+    pub investment: Vec<InvestmentTier>,
+    #[serde(default)]
+    pub redemption: Vec<RedemptionTier>,
+    #[serde(default)]
+    pub lots: LotOrder,
+    /// Applied to the investment rates, e.g. `0.1` for 1折.
+    #[serde(default = "one")]
+    pub discount: f64,
+    #[serde(default)]
+    pub minimum: f64,
+    #[serde(default)]
+    pub management: f64,
+    #[serde(default)]
+    pub custody: f64,
+    #[serde(default)]
+    pub sales_service: f64,
+}
+
+/// Either a rate for investments below `below`, or the fixed fee of the last
+/// tier.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InvestmentTier {
+    pub below: Option<f64>,
+    pub rate: Option<f64>,
+    pub fixed: Option<f64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RedemptionTier {
+    pub below_days: Option<usize>,
+    pub rate: f64,
+}
+
+fn one() -> f64 {
+    1.
+}
+
+impl Schedule {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Ok(toml::from_str(&fs::read_to_string(path)?)?)
+    }
+
+    /// Periods must be in order of their effective dates.
+    pub fn build(&self) -> Result<Box<dyn Rule>> {
+        let (first, rest) = self.periods.split_first().ok_or(Error::Invalid)?;
+        let mut rule = first.build()?;
+        let mut last = first.effective;
+        for period in rest {
+            let date = period.effective.ok_or(Error::Invalid)?;
+            if last.is_some_and(|last| last >= date) {
+                return Err(Error::Invalid);
+            }
+            rule = Box::new(Switch::new(rule, period.build()?, date));
+            last = Some(date);
+        }
+        Ok(rule)
+    }
+}
+
+impl Period {
+    fn build(&self) -> Result<Box<dyn Rule>> {
+        let mut rates = vec![];
+        let mut fixed = None;
+        for tier in &self.investment {
+            match (tier.below, tier.rate, tier.fixed) {
+                _ if fixed.is_some() => return Err(Error::Invalid),
+                (below, Some(rate), None) => rates.push((below.unwrap_or(f64::INFINITY), rate)),
+                (None, None, Some(fee)) => fixed = Some(fee),
+                _ => return Err(Error::Invalid),
+            }
+        }
+        let redemption_rates = self
+            .redemption
+            .iter()
+            .map(|tier| (tier.below_days.unwrap_or(usize::MAX), tier.rate))
+            .collect();
+        let rule: Box<dyn Rule> = Box::new(Split::new(
+            Box::new(
+                Tiered::new(rates, fixed)
+                    .with_discount(self.discount)
+                    .with_minimum(self.minimum),
+            ),
+            self.lots.build(vec![], redemption_rates),
+        ));
+        Ok(
+            if self.management == 0. && self.custody == 0. && self.sales_service == 0. {
+                rule
+            } else {
+                Box::new(
+                    Recurring::new(rule)
+                        .with_management(self.management)
+                        .with_custody(self.custody)
+                        .with_sales_service(self.sales_service),
+                )
+            },
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repository::{Order, Transaction};
+
+    #[test]
+    fn test_build() {
+        let schedule: Schedule = toml::from_str(
+            r#"
+            [[periods]]
+            investment = [{ below = 1000000.0, rate = 0.015 }, { fixed = 1000.0 }]
+            redemption = [{ below_days = 7, rate = 0.015 }]
+
+            [[periods]]
+            effective = "2021-06-01"
+            investment = [{ rate = 0.015 }]
+            discount = 0.1
+            "#,
+        )
+        .unwrap();
+        let mut rule = schedule.build().unwrap();
+        let investment = |date, investment| Order::Investment {
+            date,
+            net_asset_value: 1.0,
+            investment,
+        };
+        let date = NaiveDate::from_ymd(2021, 5, 31);
+        assert_eq!(rule.fee(investment(date, 10_000.0)), 147.78);
+        assert_eq!(rule.fee(investment(date, 2_000_000.0)), 1000.0);
+        let date = NaiveDate::from_ymd(2021, 6, 1);
+        assert_eq!(rule.fee(investment(date, 10_000.0)), 14.98);
+        assert_eq!(
+            rule.fee(Order::Redemption {
+                date,
+                net_asset_value: 1.0,
+                redemption: 100.0,
+            }),
+            0.0
+        );
+
+        let mut invalid = schedule.clone();
+        invalid.periods[1].effective = None;
+        assert!(if let Err(Error::Invalid) = invalid.build() {
+            true
+        } else {
+            false
+        });
+        let mut invalid = schedule;
+        invalid.periods[0].investment.swap(0, 1);
+        assert!(if let Err(Error::Invalid) = invalid.build() {
+            true
+        } else {
+            false
+        });
+    }
+
+    #[test]
+    fn test_open_ended() {
+        let schedule: Schedule = toml::from_str(
+            r#"
+            [[periods]]
+            redemption = [{ below_days = 7, rate = 0.015 }, { rate = 0.005 }]
+            "#,
+        )
+        .unwrap();
+        let mut repository = crate::repository::Repository::new(
+            schedule.build().unwrap(),
+            NaiveDate::from_ymd(2021, 1, 1)
+                .iter_days()
+                .take(30)
+                .map(|date| (date, 1.0))
+                .collect(),
+        )
+        .unwrap();
+        repository.invest(200.0).unwrap();
+        assert!((repository.redemption_fee(100.0).unwrap() - 1.5).abs() < 1e-9);
+        for _ in 0..20 {
+            repository.pass().unwrap();
+        }
+        assert!((repository.redemption_fee(100.0).unwrap() - 0.5).abs() < 1e-9);
+        repository.redeem(100.0).unwrap();
+        assert!(
+            if let Some(Transaction::Redemption { fee, .. }) = repository.transactions().last() {
+                (fee - 0.5).abs() < 1e-9
+            } else {
+                false
+            }
+        );
+    }
+}
@@ -0,0 +1,496 @@
+//! A REST API over the commands of the JSON protocol:
+//!
+//! - `POST /sessions` opens a session with a `Setup` body, or over the
+//!   configured data without one, and answers `201` with its `id`.
+//! - `POST /sessions/<id>/{pass,invest,redeem,pass_days,pass_until,batch,end,reset}`
+//!   with the fields of the command as body, e.g. `{"investment": 1000.0}`.
+//! - `GET /sessions/<id>` for the holding, and
+//!   `GET /sessions/<id>/{cash,transactions,window,quote,result}` with the
+//!   fields of the query as parameters, e.g. `?length=20`.
+//! - `DELETE /sessions/<id>`.
+//!
+//! Replies are those of the JSON protocol. Errors are `{"error": {...}}` with
+//! `422` for `Insufficient`, `409` for `Overflow`, `400` for invalid requests
+//! and `404` for unknown sessions.
+//!
+//! Sessions count against `max_connections` together with the connections of
+//! the line protocols, further ones are refused with `503`, and are deleted
+//! after `http_ttl` seconds without requests. With `record` set, each is
+//! logged as a connection that said hello and sent the commands in JSON, so
+//! that `record::replay` runs it again.
+
+use super::{
+    json::{self, Code, Command, Day, Failure},
+    record::Recorder,
+    text, Listener, Server, Session, BACKOFF,
+};
+use crate::error::{Error, Result};
+use hyper::{
+    header::{HeaderValue, CONTENT_TYPE},
+    server::conn::Http,
+    service::service_fn,
+    Body, Method, Request, Response, StatusCode,
+};
+use serde_json::{json, Map, Value};
+use std::{
+    collections::HashMap,
+    convert::Infallible,
+    future::Future,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+use tokio::sync::{mpsc, OwnedSemaphorePermit};
+
+/// The commands posted to a session, with their fields as body.
+const COMMANDS: [&str; 8] = [
+    "pass",
+    "invest",
+    "redeem",
+    "pass_days",
+    "pass_until",
+    "batch",
+    "end",
+    "reset",
+];
+
+/// The queries of a session, with their fields as parameters.
+const QUERIES: [&str; 5] = ["cash", "transactions", "window", "quote", "result"];
+
+/// How often sessions are checked for `http_ttl`.
+const EXPIRY: Duration = Duration::from_secs(1);
+
+/// A session with its log, holding a permit of `Server::limit`.
+struct Open {
+    session: Session,
+    recorder: Option<Recorder>,
+    _permit: OwnedSemaphorePermit,
+}
+
+impl Open {
+    /// Answers `command` as the JSON protocol does, and logs it so.
+    fn answer(&mut self, command: Command) -> Result<json::Response> {
+        let request = json::Request {
+            id: Value::Null,
+            command,
+        };
+        let line = serde_json::to_string(&request)?;
+        let response = json::answer(&mut self.session, request);
+        if let Some(recorder) = &mut self.recorder {
+            recorder.record(
+                &self.session,
+                Some(&line),
+                Some(&serde_json::to_string(&response)?),
+            )?;
+        }
+        Ok(response)
+    }
+}
+
+struct Slot {
+    open: Arc<Mutex<Open>>,
+    used: Instant,
+}
+
+/// The open sessions. Each is locked on its own, and only while a blocking
+/// task runs its command, so that the map is only locked to look it up.
+#[derive(Default)]
+struct Sessions {
+    /// Seconds since the epoch when serving started, to name the logs.
+    started: u64,
+    next: u64,
+    slots: HashMap<u64, Slot>,
+}
+
+impl Sessions {
+    fn get(&mut self, id: &str) -> Option<Arc<Mutex<Open>>> {
+        let slot = self.slots.get_mut(&id.parse().ok()?)?;
+        slot.used = Instant::now();
+        Some(slot.open.clone())
+    }
+
+    /// Deletes the sessions without requests for `ttl`.
+    fn expire(&mut self, ttl: Duration) {
+        self.slots.retain(|_, slot| slot.used.elapsed() < ttl);
+    }
+}
+
+impl Server {
+    /// Serves the REST API on `listener` until `shutdown` completes, then
+    /// waits for the requests in progress.
+    pub async fn serve_http<F>(self: Arc<Self>, listener: Listener, shutdown: F) -> Result<()>
+    where
+        F: Future<Output = ()>,
+    {
+        let sessions = Arc::new(Mutex::new(Sessions {
+            started: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |elapsed| elapsed.as_secs()),
+            ..Sessions::default()
+        }));
+        let ttl = Duration::from_secs(self.config.server.http_ttl);
+        let mut expiry = tokio::time::interval(EXPIRY);
+        let (done, mut finished) = mpsc::channel::<()>(1);
+        tokio::pin!(shutdown);
+        loop {
+            let stream = tokio::select! {
+                accepted = listener.accept() => match accepted {
+                    Ok(stream) => stream,
+                    Err(err) => {
+                        eprintln!("{}", err);
+                        tokio::time::sleep(BACKOFF).await;
+                        continue;
+                    }
+                },
+                _ = expiry.tick() => {
+                    sessions.lock().unwrap().expire(ttl);
+                    continue;
+                }
+                _ = &mut shutdown => break,
+            };
+            let server = self.clone();
+            let sessions = sessions.clone();
+            let done = done.clone();
+            tokio::spawn(async move {
+                let service = service_fn(move |request| {
+                    let server = server.clone();
+                    let sessions = sessions.clone();
+                    async move { Ok::<_, Infallible>(respond(&server, &sessions, request).await) }
+                });
+                if let Err(err) = Http::new()
+                    .http1_keep_alive(false)
+                    .serve_connection(stream, service)
+                    .await
+                {
+                    eprintln!("{}", err);
+                }
+                drop(done);
+            });
+        }
+        drop(done);
+        finished.recv().await;
+        Ok(())
+    }
+}
+
+async fn respond(
+    server: &Arc<Server>,
+    sessions: &Mutex<Sessions>,
+    request: Request<Body>,
+) -> Response<Body> {
+    let method = request.method().clone();
+    let path = request.uri().path().to_owned();
+    let query = request.uri().query().unwrap_or("").to_owned();
+    let body = match hyper::body::to_bytes(request.into_body()).await {
+        Ok(body) => body,
+        Err(err) => return failure(Code::Malformed, err.to_string()),
+    };
+    let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+    let (id, name, mut fields) = match (&method, &segments[..]) {
+        (&Method::POST, ["sessions"]) => {
+            let permit = match server.limit.clone().try_acquire_owned() {
+                Ok(permit) => permit,
+                Err(_) => return reply(StatusCode::SERVICE_UNAVAILABLE, Value::Null),
+            };
+            let (id, name) = {
+                let mut sessions = sessions.lock().unwrap();
+                let id = sessions.next;
+                sessions.next += 1;
+                (id, format!("{}-http-{}.jsonl", sessions.started, id))
+            };
+            let server = server.clone();
+            let opened =
+                tokio::task::spawn_blocking(move || open(&server, name, &body, permit)).await;
+            return match opened {
+                Ok(Ok((open, next))) => {
+                    sessions.lock().unwrap().slots.insert(
+                        id,
+                        Slot {
+                            open: Arc::new(Mutex::new(open)),
+                            used: Instant::now(),
+                        },
+                    );
+                    reply(StatusCode::CREATED, json!({ "id": id, "next": next }))
+                }
+                Ok(Err(Failure { code, message })) => failure(code, message),
+                Err(err) => failure(Code::Internal, err.to_string()),
+            };
+        }
+        (&Method::DELETE, ["sessions", id]) => {
+            let removed = id
+                .parse()
+                .ok()
+                .and_then(|id| sessions.lock().unwrap().slots.remove(&id));
+            return match removed {
+                Some(_) => reply(StatusCode::NO_CONTENT, Value::Null),
+                None => not_found(),
+            };
+        }
+        (&Method::GET, ["sessions", id]) => (*id, "holding", Map::new()),
+        (&Method::POST, ["sessions", id, name]) if COMMANDS.contains(name) => {
+            match serde_json::from_slice(&body) {
+                Ok(Value::Object(fields)) => (*id, *name, fields),
+                _ if body.is_empty() => (*id, *name, Map::new()),
+                _ => return failure(Code::Malformed, String::from("expected an object")),
+            }
+        }
+        (&Method::GET, ["sessions", id, name]) if QUERIES.contains(name) => {
+            (*id, *name, parameters(&query))
+        }
+        _ => return not_found(),
+    };
+    let open = match sessions.lock().unwrap().get(id) {
+        Some(open) => open,
+        None => return not_found(),
+    };
+    fields.insert(String::from("type"), Value::from(name));
+    let command: Command = match serde_json::from_value(Value::Object(fields)) {
+        Ok(command) => command,
+        Err(err) => return failure(Code::Malformed, err.to_string()),
+    };
+    match tokio::task::spawn_blocking(move || open.lock().unwrap().answer(command)).await {
+        Ok(Ok(response)) => outcome(response),
+        Ok(Err(err)) => error(&err),
+        Err(err) => failure(Code::Internal, err.to_string()),
+    }
+}
+
+/// A fresh session logged to `name`, opened as described by `body` if not
+/// empty, and its first day.
+fn open(
+    server: &Server,
+    name: String,
+    body: &[u8],
+    permit: OwnedSemaphorePermit,
+) -> std::result::Result<(Open, Option<Day>), Failure> {
+    let (session, mut recorder) = server.open(name)?;
+    if let Some(recorder) = &mut recorder {
+        recorder.record(&session, None, Some(&text::greeting(&session)))?;
+    }
+    let mut open = Open {
+        session,
+        recorder,
+        _permit: permit,
+    };
+    let mut commands = vec![Command::Hello {
+        version: json::VERSION,
+    }];
+    if !body.is_empty() {
+        commands.push(Command::Open {
+            setup: serde_json::from_slice(body).map_err(|err| Failure {
+                code: Code::Malformed,
+                message: err.to_string(),
+            })?,
+        });
+    }
+    for command in commands {
+        if let Some(failure) = open.answer(command)?.error {
+            return Err(failure);
+        }
+    }
+    open.session.repository()?;
+    let next = open
+        .session
+        .check()
+        .ok()
+        .map(|(date, net_asset_value)| Day {
+            date,
+            net_asset_value,
+        });
+    Ok((open, next))
+}
+
+/// The HTTP response to a response of the JSON protocol.
+fn outcome(response: json::Response) -> Response<Body> {
+    match response.error {
+        Some(Failure { code, message }) => failure(code, message),
+        None => reply(
+            StatusCode::OK,
+            serde_json::to_value(response.result).unwrap(),
+        ),
+    }
+}
+
+/// The query string as JSON fields, numbers where they parse.
+fn parameters(query: &str) -> Map<String, Value> {
+    query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let mut pair = pair.splitn(2, '=');
+            let name = pair.next().unwrap_or("");
+            let value = pair.next().unwrap_or("");
+            (
+                String::from(name),
+                serde_json::from_str(value).unwrap_or_else(|_| Value::from(value)),
+            )
+        })
+        .collect()
+}
+
+fn reply(status: StatusCode, body: Value) -> Response<Body> {
+    let mut response = Response::new(if body.is_null() {
+        Body::empty()
+    } else {
+        Body::from(body.to_string())
+    });
+    *response.status_mut() = status;
+    if !body.is_null() {
+        response
+            .headers_mut()
+            .insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+    }
+    response
+}
+
+fn failure(code: Code, message: String) -> Response<Body> {
+    let status = match code {
+        Code::Insufficient => StatusCode::UNPROCESSABLE_ENTITY,
+        Code::Overflow => StatusCode::CONFLICT,
+        Code::Invalid | Code::Malformed | Code::UnsupportedVersion => StatusCode::BAD_REQUEST,
+        Code::Internal => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+    reply(status, json!({ "error": Failure { code, message } }))
+}
+
+fn error(err: &Error) -> Response<Body> {
+    failure(Code::from(err), err.to_string())
+}
+
+fn not_found() -> Response<Body> {
+    reply(StatusCode::NOT_FOUND, Value::Null)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+    use std::fs;
+
+    async fn call(
+        server: &Arc<Server>,
+        sessions: &Mutex<Sessions>,
+        method: Method,
+        uri: &str,
+        body: &str,
+    ) -> (StatusCode, Value) {
+        let request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .body(Body::from(String::from(body)))
+            .unwrap();
+        let response = respond(server, sessions, request).await;
+        let status = response.status();
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+    }
+
+    #[tokio::test]
+    async fn test_respond() {
+        let server = Arc::new(Server::with_history(
+            toml::from_str("[data]\n[cash]\ninitial = 100.0").unwrap(),
+            Some(
+                NaiveDate::from_ymd(2021, 1, 1)
+                    .iter_days()
+                    .take(3)
+                    .map(|date| (date, 1.0))
+                    .collect(),
+            ),
+        ));
+        let sessions = Mutex::new(Sessions::default());
+        let (status, body) = call(&server, &sessions, Method::POST, "/sessions", "").await;
+        assert_eq!(status, StatusCode::CREATED);
+        assert_eq!(
+            body,
+            json!({"id": 0, "next": {"date": "2021-01-01", "net_asset_value": 1.0}})
+        );
+        let (status, _) = call(
+            &server,
+            &sessions,
+            Method::POST,
+            "/sessions/0/invest",
+            r#"{"investment": 100.0}"#,
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, body) = call(
+            &server,
+            &sessions,
+            Method::POST,
+            "/sessions/0/invest",
+            r#"{"investment": 1.0}"#,
+        )
+        .await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body["error"]["code"], "insufficient");
+        let (status, body) = call(&server, &sessions, Method::GET, "/sessions/0/cash", "").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["cash"], 0.0);
+        let (status, _) = call(&server, &sessions, Method::GET, "/sessions/1", "").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let (status, _) = call(&server, &sessions, Method::DELETE, "/sessions/0", "").await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        let (status, _) = call(&server, &sessions, Method::GET, "/sessions/0", "").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_limit() {
+        let server = Arc::new(Server::with_history(
+            toml::from_str("[data]\n[server]\nmax_connections = 1").unwrap(),
+            Some(vec![(NaiveDate::from_ymd(2021, 1, 1), 1.0)]),
+        ));
+        let sessions = Mutex::new(Sessions::default());
+        let (status, _) = call(&server, &sessions, Method::POST, "/sessions", "").await;
+        assert_eq!(status, StatusCode::CREATED);
+        let (status, _) = call(&server, &sessions, Method::POST, "/sessions", "").await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        // the connections of the line protocols wait for the same permit
+        assert!(server.limit.clone().try_acquire_owned().is_err());
+
+        sessions.lock().unwrap().expire(Duration::from_secs(60));
+        let (status, _) = call(&server, &sessions, Method::GET, "/sessions/0", "").await;
+        assert_eq!(status, StatusCode::OK);
+        sessions.lock().unwrap().expire(Duration::from_secs(0));
+        let (status, _) = call(&server, &sessions, Method::GET, "/sessions/0", "").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let (status, body) = call(&server, &sessions, Method::POST, "/sessions", "").await;
+        assert_eq!(status, StatusCode::CREATED);
+        assert_eq!(body["id"], 1);
+    }
+
+    #[tokio::test]
+    async fn test_record() {
+        let directory = std::env::temp_dir().join(format!("fund-http-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        fs::write(
+            directory.join("a.csv"),
+            "date,nav\n2021-01-01,1.0\n2021-01-02,1.1\n2021-01-03,1.2\n",
+        )
+        .unwrap();
+        let config = format!(
+            "[data]\npath = {:?}\n[cash]\ninitial = 100.0\n[server]\nrecord = {:?}",
+            directory.join("a.csv"),
+            directory
+        );
+        let server = Arc::new(Server::new(toml::from_str(&config).unwrap()).unwrap());
+        let sessions = Mutex::new(Sessions::default());
+        call(&server, &sessions, Method::POST, "/sessions", "").await;
+        call(
+            &server,
+            &sessions,
+            Method::POST,
+            "/sessions/0/invest",
+            r#"{"investment": 100.0}"#,
+        )
+        .await;
+        call(&server, &sessions, Method::GET, "/sessions/0/cash", "").await;
+        call(&server, &sessions, Method::DELETE, "/sessions/0", "").await;
+        let path = directory.join("0-http-0.jsonl");
+        assert_eq!(fs::read_to_string(&path).unwrap().lines().count(), 5);
+        assert_eq!(
+            crate::server::record::replay(&path).unwrap(),
+            crate::server::record::Outcome::Replayed
+        );
+        fs::remove_dir_all(directory).unwrap();
+    }
+}
@@ -0,0 +1,629 @@
+//! The JSON-lines protocol, chosen by sending a hello in place of the first
+//! text command, e.g.
+//!
+//! ```text
+//! > {"id": 1, "type": "hello", "version": 1}
+//! < {"id": 1, "result": {"type": "hello", "version": 1, "next": {"date": "2021-01-01", "net_asset_value": 1.0}}}
+//! > {"id": 2, "type": "invest", "investment": 1000.0}
+//! < {"id": 2, "result": {"type": "confirmed", "transaction": {...}, "fee": 15.0, "daily_info": {...}, "next": {...}}}
+//! > {"id": 3, "type": "redeem", "redemption": 1e9}
+//! < {"id": 3, "error": {"code": "insufficient", "message": "Insufficient"}}
+//! ```
+//!
+//! Every response echoes the id of its request. `next` is null after the last
+//! day. Unlike `e`, `end` keeps the connection open, so that the client can
+//! query the result, `reset` the simulation or `open` another one, e.g.
+//!
+//! ```text
+//! > {"type": "open", "fund": "110022", "start": "2020-01-01", "cash": 100000.0}
+//! < {"id": null, "result": {"type": "opened", "next": {"date": "2020-01-02", "net_asset_value": 1.0}}}
+//! ```
+//!
+//! as described by `Setup`.
+//!
+//! `pass_days`, `pass_until` and `batch` simulate several days at once, like
+//! `p<days>`, `u<date>` and `;` in `text`, and are answered with one `advanced`.
+//!
+//! The queries `holding`, `cash`, `transactions`, `window`, `quote` and `result`
+//! read the state without simulating a day.
+
+use super::session::{Session, Setup};
+use crate::{
+    backtest::Action,
+    error::{Error, Result},
+    metrics::{self, Metrics},
+    repository::{DailyInfo, Repository, Transaction},
+};
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+pub const VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Request {
+    #[serde(default)]
+    pub id: Value,
+    #[serde(flatten)]
+    pub command: Command,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Command {
+    Hello {
+        version: u32,
+    },
+    Pass,
+    Invest {
+        investment: f64,
+    },
+    Redeem {
+        redemption: f64,
+    },
+    /// Passes up to `days` days, as `p<days>`.
+    PassDays {
+        days: usize,
+    },
+    /// Passes the days before `date`, as `u<date>`.
+    PassUntil {
+        date: NaiveDate,
+    },
+    /// Carries out `orders` on consecutive days, all of them or none.
+    Batch {
+        orders: Vec<Step>,
+    },
+    /// Replaces the simulation with a fund of the server's directory.
+    Open {
+        #[serde(flatten)]
+        setup: Setup,
+    },
+    /// Starts the simulation over.
+    Reset,
+    /// Passes the remaining days, as `e`.
+    End,
+    Holding,
+    Cash,
+    /// The transactions from the `from`th on.
+    Transactions {
+        #[serde(default)]
+        from: usize,
+    },
+    /// The last `length` records up to the day to trade on.
+    Window {
+        length: usize,
+    },
+    /// The fee of an investment or a redemption today, without trading.
+    Quote {
+        investment: Option<f64>,
+        redemption: Option<f64>,
+    },
+    /// The metrics, transactions and equity curve so far.
+    Result,
+}
+
+/// An order of a batch, e.g. `{"type": "invest", "investment": 1000.0}`.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Step {
+    Pass,
+    Invest { investment: f64 },
+    Redeem { redemption: f64 },
+}
+
+impl From<Step> for Action {
+    fn from(step: Step) -> Self {
+        match step {
+            Step::Pass => Action::Pass,
+            Step::Invest { investment } => Action::Invest(investment),
+            Step::Redeem { redemption } => Action::Redeem(redemption),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Response {
+    pub id: Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<Reply>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<Failure>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Reply {
+    Hello {
+        version: u32,
+        next: Option<Day>,
+    },
+    /// The day just simulated and the one to trade on next.
+    Confirmed {
+        transaction: Option<Transaction>,
+        fee: f64,
+        daily_info: DailyInfo,
+        next: Option<Day>,
+    },
+    /// The days just simulated at once, with their transactions and fees.
+    /// `daily_info` is null if still before the first day.
+    Advanced {
+        days: usize,
+        transactions: Vec<Transaction>,
+        fee: f64,
+        daily_info: Option<DailyInfo>,
+        next: Option<Day>,
+    },
+    Opened {
+        next: Option<Day>,
+    },
+    Ended {
+        daily_info: Option<DailyInfo>,
+    },
+    /// `daily_info` is null before the first day.
+    Holding {
+        daily_info: Option<DailyInfo>,
+    },
+    /// `cash` is null if unlimited.
+    Cash {
+        cash: Option<f64>,
+    },
+    Transactions {
+        transactions: Vec<Transaction>,
+    },
+    Window {
+        records: Vec<Day>,
+    },
+    Quote {
+        fee: f64,
+    },
+    Result {
+        metrics: Metrics,
+        transactions: Vec<Transaction>,
+        equity_curve: Vec<(NaiveDate, f64)>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Day {
+    pub date: NaiveDate,
+    pub net_asset_value: f64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Failure {
+    pub code: Code,
+    pub message: String,
+}
+
+impl From<Error> for Failure {
+    fn from(err: Error) -> Self {
+        Self {
+            code: Code::from(&err),
+            message: err.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Code {
+    Insufficient,
+    Overflow,
+    Invalid,
+    /// The line is not a request.
+    Malformed,
+    UnsupportedVersion,
+    Internal,
+}
+
+impl From<&Error> for Code {
+    fn from(err: &Error) -> Self {
+        match err {
+            Error::Insufficient => Code::Insufficient,
+            Error::Overflow => Code::Overflow,
+            Error::Invalid => Code::Invalid,
+            Error::Json(_) => Code::Malformed,
+            _ => Code::Internal,
+        }
+    }
+}
+
+impl Response {
+    fn ok(id: Value, reply: Reply) -> Self {
+        Self {
+            id,
+            result: Some(reply),
+            error: None,
+        }
+    }
+
+    fn err(id: Value, code: Code, message: String) -> Self {
+        Self {
+            id,
+            result: None,
+            error: Some(Failure { code, message }),
+        }
+    }
+}
+
+/// The version of `line` if it is a hello, supported or not.
+pub fn hello(line: &str) -> Option<u32> {
+    match serde_json::from_str::<Request>(line) {
+        Ok(Request {
+            command: Command::Hello { version },
+            ..
+        }) => Some(version),
+        _ => None,
+    }
+}
+
+/// Whether `line` is a hello of `VERSION`, which switches a connection to
+/// this protocol.
+pub fn is_hello(line: &str) -> bool {
+    hello(line) == Some(VERSION)
+}
+
+/// The response to `line`.
+pub fn handle(session: &mut Session, line: &str) -> String {
+    let response = match serde_json::from_str::<Request>(line) {
+        Ok(request) => answer(session, request),
+        Err(err) => Response::err(Value::Null, Code::Malformed, err.to_string()),
+    };
+    serde_json::to_string(&response).unwrap()
+}
+
+/// The response to `request`, for this protocol and `http`.
+pub fn answer(session: &mut Session, request: Request) -> Response {
+    match request {
+        Request {
+            id,
+            command: Command::Hello { version },
+        } if version != VERSION => Response::err(
+            id,
+            Code::UnsupportedVersion,
+            format!("supported version: {}", VERSION),
+        ),
+        Request { id, command } => match execute(session, command) {
+            Ok(reply) => Response::ok(id, reply),
+            Err(err) => Response::err(id, Code::from(&err), err.to_string()),
+        },
+    }
+}
+
+/// Runs `command` on `session`.
+fn execute(session: &mut Session, command: Command) -> Result<Reply> {
+    Ok(match command {
+        Command::Hello { version } => Reply::Hello {
+            version,
+            next: next(session),
+        },
+        Command::Open { setup } => {
+            session.open(&setup)?;
+            Reply::Opened {
+                next: next(session),
+            }
+        }
+        Command::Reset => {
+            session.reset()?;
+            Reply::Opened {
+                next: next(session),
+            }
+        }
+        Command::End => {
+            session.finish()?;
+            Reply::Ended {
+                daily_info: session.repository()?.daily_infos().last().cloned(),
+            }
+        }
+        Command::Pass => {
+            session.pass()?;
+            confirmed(session.repository()?)
+        }
+        Command::Invest { investment } => {
+            session.invest(investment)?;
+            confirmed(session.repository()?)
+        }
+        Command::Redeem { redemption } => {
+            session.redeem(redemption)?;
+            confirmed(session.repository()?)
+        }
+        Command::PassDays { days } => {
+            let before = Before::new(session)?;
+            session.pass_days(days)?;
+            before.advanced(session.repository()?)
+        }
+        Command::PassUntil { date } => {
+            let before = Before::new(session)?;
+            session.pass_until(date)?;
+            before.advanced(session.repository()?)
+        }
+        Command::Batch { orders } => {
+            let before = Before::new(session)?;
+            session.apply(&orders.into_iter().map(Action::from).collect::<Vec<_>>())?;
+            before.advanced(session.repository()?)
+        }
+        Command::Holding => Reply::Holding {
+            daily_info: session.repository()?.daily_infos().last().cloned(),
+        },
+        Command::Cash => Reply::Cash {
+            cash: Some(session.repository()?.cash()).filter(|cash| cash.is_finite()),
+        },
+        Command::Transactions { from } => Reply::Transactions {
+            transactions: session
+                .repository()?
+                .transactions()
+                .get(from..)
+                .unwrap_or_default()
+                .to_vec(),
+        },
+        Command::Window { length } => Reply::Window {
+            records: session
+                .repository()?
+                .window(length)
+                .iter()
+                .map(|&(date, net_asset_value)| Day {
+                    date,
+                    net_asset_value,
+                })
+                .collect(),
+        },
+        Command::Quote {
+            investment: Some(investment),
+            redemption: None,
+        } => Reply::Quote {
+            fee: session.repository()?.investment_fee(investment)?,
+        },
+        Command::Quote {
+            investment: None,
+            redemption: Some(redemption),
+        } => Reply::Quote {
+            fee: session.repository()?.redemption_fee(redemption)?,
+        },
+        Command::Quote { .. } => return Err(Error::Invalid),
+        Command::Result => {
+            let repository = session.repository()?;
+            Reply::Result {
+                metrics: Metrics::new(repository),
+                transactions: repository.transactions().to_vec(),
+                equity_curve: metrics::equity_curve(repository),
+            }
+        }
+    })
+}
+
+fn next(session: &Session) -> Option<Day> {
+    session.check().ok().map(|(date, net_asset_value)| Day {
+        date,
+        net_asset_value,
+    })
+}
+
+/// The reply for the day just simulated.
+fn confirmed(repository: &Repository) -> Reply {
+    let daily_info = repository.daily_infos().last().unwrap().clone();
+    let transaction = daily_info
+        .transaction_id()
+        .map(|i| repository.transactions()[i].clone());
+    Reply::Confirmed {
+        fee: transaction.as_ref().map_or(0., fee),
+        transaction,
+        next: repository.check().ok().map(|(date, net_asset_value)| Day {
+            date,
+            net_asset_value,
+        }),
+        daily_info,
+    }
+}
+
+fn fee(transaction: &Transaction) -> f64 {
+    match *transaction {
+        Transaction::Investment { fee, .. }
+        | Transaction::Redemption { fee, .. }
+        | Transaction::Switch { fee, .. } => fee,
+    }
+}
+
+/// How far a session was before simulating several days at once.
+struct Before {
+    days: usize,
+    transactions: usize,
+}
+
+impl Before {
+    fn new(session: &Session) -> Result<Self> {
+        let repository = session.repository()?;
+        Ok(Self {
+            days: repository.daily_infos().len(),
+            transactions: repository.transactions().len(),
+        })
+    }
+
+    fn advanced(&self, repository: &Repository) -> Reply {
+        let transactions = repository.transactions()[self.transactions..].to_vec();
+        Reply::Advanced {
+            days: repository.daily_infos().len() - self.days,
+            fee: transactions
+                .iter()
+                .fold(0., |sum, transaction| sum + fee(transaction)),
+            transactions,
+            daily_info: repository.daily_infos().last().cloned(),
+            next: repository.check().ok().map(|(date, net_asset_value)| Day {
+                date,
+                net_asset_value,
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{repository::Repository, rules::Fifo};
+
+    #[test]
+    fn test_handle() {
+        let mut session = Session::new(
+            Repository::new(
+                Box::new(|_| 1.0),
+                NaiveDate::from_ymd(2021, 1, 1)
+                    .iter_days()
+                    .take(2)
+                    .map(|date| (date, 1.0))
+                    .collect(),
+            )
+            .unwrap(),
+        );
+        let line = r#"{"id": 1, "type": "hello", "version": 1}"#;
+        assert!(is_hello(line));
+        assert!(!is_hello("p"));
+        assert!(!is_hello(r#"{"id": 1, "type": "hello", "version": 2}"#));
+        assert_eq!(hello(r#"{"type": "hello", "version": 2}"#), Some(2));
+        let response = handle(&mut session, line);
+        let response: Value = serde_json::from_str(&response).unwrap();
+        assert_eq!(response["id"], 1);
+        assert_eq!(response["result"]["next"]["date"], "2021-01-01");
+
+        let response = handle(
+            &mut session,
+            r#"{"id": "a", "type": "redeem", "redemption": 1}"#,
+        );
+        let response: Value = serde_json::from_str(&response).unwrap();
+        assert_eq!(response["id"], "a");
+        assert_eq!(response["error"]["code"], "insufficient");
+
+        let response = handle(&mut session, "i100");
+        let response: Value = serde_json::from_str(&response).unwrap();
+        assert_eq!(response["error"]["code"], "malformed");
+
+        let response = handle(
+            &mut session,
+            r#"{"id": 2, "type": "invest", "investment": 100}"#,
+        );
+        let response: Value = serde_json::from_str(&response).unwrap();
+        let result = &response["result"];
+        assert_eq!(result["type"], "confirmed");
+        assert_eq!(result["fee"], 1.0);
+        assert_eq!(result["transaction"]["type"], "investment");
+        assert_eq!(result["transaction"]["share"], 99.0);
+        assert_eq!(result["daily_info"]["holding_share"], 99.0);
+        assert_eq!(result["next"]["date"], "2021-01-02");
+
+        let response = handle(&mut session, r#"{"id": 3, "type": "pass"}"#);
+        let response: Value = serde_json::from_str(&response).unwrap();
+        assert_eq!(response["result"]["transaction"], Value::Null);
+        assert_eq!(response["result"]["next"], Value::Null);
+    }
+
+    #[test]
+    fn test_query() {
+        let mut session = Session::new(
+            Repository::new(
+                Box::new(Fifo::new(vec![], vec![(7, 0.015)])),
+                NaiveDate::from_ymd(2021, 1, 1)
+                    .iter_days()
+                    .take(3)
+                    .map(|date| (date, 1.0))
+                    .collect(),
+            )
+            .unwrap()
+            .with_cash(1000.0),
+        );
+        let mut query = |line| {
+            let response = handle(&mut session, line);
+            serde_json::from_str::<Value>(&response).unwrap()["result"].clone()
+        };
+        assert_eq!(query(r#"{"type": "holding"}"#)["daily_info"], Value::Null);
+        query(r#"{"type": "invest", "investment": 100}"#);
+        assert_eq!(
+            query(r#"{"type": "holding"}"#)["daily_info"]["holding_share"],
+            100.0
+        );
+        assert_eq!(query(r#"{"type": "cash"}"#)["cash"], 900.0);
+        let transactions = query(r#"{"type": "transactions"}"#);
+        assert_eq!(transactions["transactions"].as_array().unwrap().len(), 1);
+        let transactions = query(r#"{"type": "transactions", "from": 1}"#);
+        assert_eq!(transactions["transactions"], serde_json::json!([]));
+        let window = query(r#"{"type": "window", "length": 5}"#);
+        assert_eq!(window["records"].as_array().unwrap().len(), 2);
+        assert_eq!(window["records"][1]["date"], "2021-01-02");
+        let quote = query(r#"{"type": "quote", "redemption": 100}"#);
+        assert_eq!(quote["fee"], 1.5);
+        assert_eq!(query(r#"{"type": "quote", "redemption": 100}"#)["fee"], 1.5);
+        assert_eq!(query(r#"{"type": "quote"}"#), Value::Null);
+        assert_eq!(
+            query(r#"{"type": "holding"}"#)["daily_info"]["holding_share"],
+            100.0
+        );
+    }
+
+    #[test]
+    fn test_advance() {
+        let mut session = Session::new(
+            Repository::new(
+                Box::new(|_| 1.0),
+                NaiveDate::from_ymd(2021, 1, 1)
+                    .iter_days()
+                    .take(10)
+                    .map(|date| (date, 1.0))
+                    .collect(),
+            )
+            .unwrap()
+            .with_cash(150.0),
+        );
+        let mut request = |line| {
+            let response = handle(&mut session, line);
+            serde_json::from_str::<Value>(&response).unwrap()
+        };
+        let result = &request(r#"{"type": "pass_days", "days": 2}"#)["result"];
+        assert_eq!(result["days"], 2);
+        assert_eq!(result["next"]["date"], "2021-01-03");
+        let result = &request(
+            r#"{"type": "batch", "orders": [{"type": "invest", "investment": 101}, {"type": "pass"}, {"type": "redeem", "redemption": 50}]}"#,
+        )["result"];
+        assert_eq!(result["type"], "advanced");
+        assert_eq!(result["days"], 3);
+        assert_eq!(result["transactions"].as_array().unwrap().len(), 2);
+        assert_eq!(result["fee"], 2.0);
+        assert_eq!(result["daily_info"]["holding_share"], 50.0);
+        assert_eq!(result["next"]["date"], "2021-01-06");
+        let response = request(
+            r#"{"type": "batch", "orders": [{"type": "invest", "investment": 100}, {"type": "invest", "investment": 100}]}"#,
+        );
+        assert_eq!(response["error"]["code"], "insufficient");
+        let result = &request(r#"{"type": "pass_until", "date": "2021-01-09"}"#)["result"];
+        assert_eq!(result["days"], 3);
+        assert!(result["transactions"].as_array().unwrap().is_empty());
+        let result = &request(r#"{"type": "pass_days", "days": 5}"#)["result"];
+        assert_eq!(result["days"], 2);
+        assert_eq!(result["next"], Value::Null);
+    }
+
+    #[test]
+    fn test_advance_nothing() {
+        let mut session = Session::new(
+            Repository::new(
+                Box::new(|_| 1.0),
+                NaiveDate::from_ymd(2021, 1, 1)
+                    .iter_days()
+                    .take(3)
+                    .map(|date| (date, 1.0))
+                    .collect(),
+            )
+            .unwrap(),
+        );
+        let mut query = |line| {
+            let response = handle(&mut session, line);
+            serde_json::from_str::<Value>(&response).unwrap()["result"].clone()
+        };
+        for line in &[
+            r#"{"type": "pass_until", "date": "2020-01-01"}"#,
+            r#"{"type": "batch", "orders": []}"#,
+        ] {
+            let result = query(line);
+            assert_eq!(result["type"], "advanced");
+            assert_eq!(result["days"], 0);
+            assert_eq!(result["daily_info"], Value::Null);
+            assert_eq!(result["next"]["date"], "2021-01-01");
+        }
+    }
+}
@@ -0,0 +1,46 @@
+use std::io;
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    net::TcpListener,
+};
+
+/// Where a server accepts its connections.
+pub enum Listener {
+    Tcp(TcpListener),
+    #[cfg(unix)]
+    Unix(tokio::net::UnixListener),
+}
+
+/// A connection accepted by a `Listener`.
+pub trait Stream: AsyncRead + AsyncWrite + Send + Unpin {}
+
+impl<T: AsyncRead + AsyncWrite + Send + Unpin> Stream for T {}
+
+impl Listener {
+    pub async fn accept(&self) -> io::Result<Box<dyn Stream>> {
+        Ok(match self {
+            Listener::Tcp(listener) => Box::new(listener.accept().await?.0),
+            #[cfg(unix)]
+            Listener::Unix(listener) => Box::new(listener.accept().await?.0),
+        })
+    }
+
+    /// The address actually bound, with the port chosen for port 0, e.g.
+    /// `127.0.0.1:41234` or `unix:/tmp/fund.sock`.
+    pub fn local_addr(&self) -> io::Result<String> {
+        Ok(match self {
+            Listener::Tcp(listener) => listener.local_addr()?.to_string(),
+            #[cfg(unix)]
+            Listener::Unix(listener) => match listener.local_addr()?.as_pathname() {
+                Some(path) => format!("unix:{}", path.display()),
+                None => String::from("unix:"),
+            },
+        })
+    }
+}
+
+impl From<TcpListener> for Listener {
+    fn from(listener: TcpListener) -> Self {
+        Listener::Tcp(listener)
+    }
+}
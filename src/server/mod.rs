@@ -0,0 +1,668 @@
+pub use listener::{Listener, Stream};
+pub use session::{Session, Setup};
+
+pub mod http;
+pub mod json;
+pub mod record;
+pub mod text;
+
+mod listener;
+mod session;
+
+use crate::{
+    config::{CashConfig, DataConfig, RuleConfig},
+    data,
+    error::{Error, Result},
+    repository::{Repository, Rule},
+};
+use chrono::NaiveDate;
+use record::Recorder;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    future::Future,
+    io::{BufRead, BufReader, BufWriter, Write},
+    net::TcpListener,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt},
+    sync::{mpsc, watch, Semaphore},
+    time::Instant,
+};
+
+/// A long-running simulation server, e.g.
+///
+/// ```toml
+/// [data]
+/// path = "110022.csv"
+/// start = "2020-01-01"
+///
+/// [rule]
+/// redemption_rates = [[7, 0.015], [365, 0.005]]
+///
+/// [cash]
+/// initial = 100000.0
+///
+/// [funds]
+/// directory = "funds"
+///
+/// [server]
+/// address = "127.0.0.1"
+/// port = 8000
+/// http_port = 8080
+/// http_ttl = 600
+/// max_connections = 64
+/// record = "logs"
+/// announce = "addresses.txt"
+/// ```
+///
+/// Every connection gets its own repository over the same data and rule, and
+/// may open another fund of `[funds]`, see `Setup`. Either may be absent.
+/// Relative paths are resolved against the directory of the file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ServerConfig {
+    #[serde(default)]
+    pub data: DataConfig,
+    #[serde(default)]
+    pub rule: RuleConfig,
+    #[serde(default)]
+    pub cash: CashConfig,
+    pub funds: Option<FundsConfig>,
+    #[serde(default)]
+    pub server: ServeConfig,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FundsConfig {
+    pub directory: PathBuf,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ServeConfig {
+    #[serde(flatten)]
+    pub listen: ListenConfig,
+    /// The port of the REST API in `http`, if any.
+    pub http_port: Option<u16>,
+    /// Seconds a session of the REST API is kept without requests.
+    #[serde(default = "default_http_ttl")]
+    pub http_ttl: u64,
+    /// Further clients wait until a connection closes, and further sessions
+    /// of the REST API are refused until one is deleted or expires.
+    #[serde(default = "default_max_connections")]
+    pub max_connections: usize,
+    /// The directory to log each connection to, see `record`.
+    pub record: Option<PathBuf>,
+}
+
+impl Default for ServeConfig {
+    fn default() -> Self {
+        Self {
+            listen: ListenConfig::default(),
+            http_port: None,
+            http_ttl: default_http_ttl(),
+            max_connections: default_max_connections(),
+            record: None,
+        }
+    }
+}
+
+impl ServeConfig {
+    /// Binds the listener of the REST API, if any.
+    pub async fn bind_http(&self) -> Result<Option<Listener>> {
+        Ok(match self.http_port {
+            Some(port) => Some(
+                tokio::net::TcpListener::bind((self.listen.address.as_str(), port))
+                    .await?
+                    .into(),
+            ),
+            None => None,
+        })
+    }
+}
+
+/// Where to listen, for `[server]` and `stream::StreamConfig`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ListenConfig {
+    /// The address to bind, e.g. `0.0.0.0` to accept remote clients.
+    #[serde(default = "default_address")]
+    pub address: String,
+    /// 0 lets the system choose a free port, see `announce`.
+    #[serde(default = "default_port")]
+    pub port: u16,
+    /// A Unix domain socket to listen on in place of `port`.
+    pub unix: Option<PathBuf>,
+    /// A file to write the addresses actually bound to, besides stdout.
+    pub announce: Option<PathBuf>,
+}
+
+impl Default for ListenConfig {
+    fn default() -> Self {
+        Self {
+            address: default_address(),
+            port: default_port(),
+            unix: None,
+            announce: None,
+        }
+    }
+}
+
+impl ListenConfig {
+    /// Resolves `unix` and `announce` against `base`.
+    pub fn resolve(&mut self, base: &Path) {
+        if let Some(unix) = &mut self.unix {
+            *unix = base.join(&unix);
+        }
+        if let Some(announce) = &mut self.announce {
+            *announce = base.join(&announce);
+        }
+    }
+
+    /// Binds `address` and `port`, or `unix` if set.
+    pub async fn bind(&self) -> Result<Listener> {
+        match &self.unix {
+            #[cfg(unix)]
+            Some(path) => Ok(Listener::Unix(tokio::net::UnixListener::bind(path)?)),
+            #[cfg(not(unix))]
+            Some(_) => Err(Error::Invalid),
+            None => Ok(
+                tokio::net::TcpListener::bind((self.address.as_str(), self.port))
+                    .await?
+                    .into(),
+            ),
+        }
+    }
+
+    /// Writes one `<protocol> <address>` line per listener to `out` and to
+    /// `announce`, if set, all at once so that nobody reads half of them.
+    pub fn announce<W: Write>(&self, mut out: W, listeners: &[(&str, &Listener)]) -> Result<()> {
+        let mut lines = String::new();
+        for (protocol, listener) in listeners {
+            lines += &format!("{} {}\n", protocol, listener.local_addr()?);
+        }
+        out.write_all(lines.as_bytes())?;
+        out.flush()?;
+        if let Some(path) = &self.announce {
+            let partial = path.with_extension("partial");
+            fs::write(&partial, lines)?;
+            fs::rename(partial, path)?;
+        }
+        Ok(())
+    }
+}
+
+fn default_address() -> String {
+    String::from("127.0.0.1")
+}
+
+fn default_port() -> u16 {
+    8000
+}
+
+fn default_http_ttl() -> u64 {
+    600
+}
+
+fn default_max_connections() -> usize {
+    64
+}
+
+impl ServerConfig {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        // absolute, so that `record` logs paths that replay from anywhere
+        let path = fs::canonicalize(path)?;
+        let mut config: ServerConfig = toml::from_str(&fs::read_to_string(&path)?)?;
+        let base = path.parent().unwrap_or_else(|| Path::new(""));
+        if let Some(data) = &mut config.data.path {
+            *data = base.join(&data);
+        }
+        config.rule.resolve(base)?;
+        if let Some(funds) = &mut config.funds {
+            funds.directory = base.join(&funds.directory);
+        }
+        if let Some(record) = &mut config.server.record {
+            *record = base.join(&record);
+        }
+        config.server.listen.resolve(base);
+        Ok(config)
+    }
+}
+
+pub struct Server {
+    config: ServerConfig,
+    history: Option<Vec<(NaiveDate, f64)>>,
+    /// The connections and the sessions of the REST API, together at most
+    /// `max_connections`.
+    limit: Arc<Semaphore>,
+}
+
+impl Server {
+    pub fn new(config: ServerConfig) -> Result<Self> {
+        let history = match &config.data.path {
+            Some(path) => Some(data::load(path)?),
+            None if config.funds.is_some() => None,
+            None => return Err(Error::Invalid),
+        };
+        let server = Self::with_history(config, history);
+        server.session()?;
+        Ok(server)
+    }
+
+    fn with_history(config: ServerConfig, history: Option<Vec<(NaiveDate, f64)>>) -> Self {
+        Self {
+            limit: Arc::new(Semaphore::new(config.server.max_connections)),
+            config,
+            history,
+        }
+    }
+
+    /// A fresh session over the configured data and rule, if any.
+    fn session(&self) -> Result<Session> {
+        let session = match &self.history {
+            Some(history) => {
+                let repository = Repository::new(self.config.rule.build()?, history.clone())?
+                    .with_range(
+                        self.config.data.start,
+                        self.config.data.end,
+                        self.config.data.warm_up.unwrap_or(0),
+                    )?;
+                Session::new(match self.config.cash.initial {
+                    Some(cash) => repository.with_cash(cash),
+                    None => repository,
+                })
+            }
+            None => Session::default(),
+        };
+        Ok(match &self.config.funds {
+            Some(funds) => session.with_directory(funds.directory.clone()),
+            None => session,
+        })
+    }
+
+    /// A fresh session and, if recording, its log named `name`.
+    fn open(&self, name: String) -> Result<(Session, Option<Recorder>)> {
+        let recorder = match &self.config.server.record {
+            Some(directory) => Some(Recorder::create(directory.join(name), &self.config)?),
+            None => None,
+        };
+        Ok((self.session()?, recorder))
+    }
+
+    /// Serves every client of `listener` concurrently until `shutdown`
+    /// completes, then waits for the open sessions to answer the commands in
+    /// flight and end.
+    pub async fn serve<F>(self: Arc<Self>, listener: Listener, shutdown: F) -> Result<()>
+    where
+        F: Future<Output = ()>,
+    {
+        let (notify, closing) = watch::channel(false);
+        let (done, mut finished) = mpsc::channel::<()>(1);
+        let started = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_secs());
+        tokio::pin!(shutdown);
+        for id in 0.. {
+            let permit = tokio::select! {
+                permit = self.limit.clone().acquire_owned() => permit.map_err(|_| Error::Invalid)?,
+                _ = &mut shutdown => break,
+            };
+            let stream = tokio::select! {
+                accepted = listener.accept() => match accepted {
+                    Ok(stream) => stream,
+                    Err(err) => {
+                        eprintln!("{}", err);
+                        drop(permit);
+                        tokio::time::sleep(BACKOFF).await;
+                        continue;
+                    }
+                },
+                _ = &mut shutdown => break,
+            };
+            let server = self.clone();
+            let closing = closing.clone();
+            let done = done.clone();
+            tokio::spawn(async move {
+                let res = match server.open(format!("{}-{}.jsonl", started, id)) {
+                    Ok((session, recorder)) => connection(stream, session, recorder, closing).await,
+                    Err(err) => Err(err),
+                };
+                if let Err(err) = res {
+                    eprintln!("{}", err);
+                }
+                drop(permit);
+                drop(done);
+            });
+        }
+        notify.send(true).ok();
+        drop(done);
+        finished.recv().await;
+        Ok(())
+    }
+
+    /// Serves a single connection of `listener` and returns its repository,
+    /// as the demo does.
+    pub fn run(
+        rule: Box<dyn Rule>,
+        net_asset_value_history: Vec<(NaiveDate, f64)>,
+        listener: TcpListener,
+    ) -> Result<Repository> {
+        let mut session = Session::new(Repository::new(rule, net_asset_value_history)?);
+        let (stream, _) = listener.accept()?;
+        let reader = BufReader::new(stream.try_clone()?);
+        let mut writer = BufWriter::new(stream);
+        writeln!(&mut writer, "{}", text::greeting(&session))?;
+        writer.flush()?;
+        for line in reader.lines() {
+            let (reply, end) = text::handle(&mut session, &line?);
+            if let Some(reply) = reply {
+                writeln!(&mut writer, "{}", reply)?;
+                writer.flush()?;
+            }
+            if end {
+                break;
+            }
+        }
+        session.into_repository().ok_or(Error::Invalid)
+    }
+}
+
+/// How long a connection is kept open for the commands in flight once the
+/// server closes.
+const GRACE: Duration = Duration::from_secs(1);
+
+/// How long to wait after failing to accept a connection, e.g. for lack of
+/// file descriptors, before trying again.
+pub(crate) const BACKOFF: Duration = Duration::from_millis(100);
+
+/// Speaks the text protocol, or the JSON one after a hello, with one client
+/// until either side ends the connection, or for at most `GRACE` after the
+/// server closed.
+async fn connection<S>(
+    stream: S,
+    mut session: Session,
+    mut recorder: Option<Recorder>,
+    mut closing: watch::Receiver<bool>,
+) -> Result<()>
+where
+    S: AsyncRead + AsyncWrite,
+{
+    let (reader, mut writer) = tokio::io::split(stream);
+    let mut lines = tokio::io::BufReader::new(reader).lines();
+    let greeting = text::greeting(&session);
+    if let Some(recorder) = &mut recorder {
+        recorder.record(&session, None, Some(&greeting))?;
+    }
+    writer
+        .write_all(format!("{}\n", greeting).as_bytes())
+        .await?;
+    let mut is_json = false;
+    let mut deadline = None;
+    loop {
+        let line = if *closing.borrow() {
+            // Answers what the client sent before it noticed the server closing.
+            let deadline = *deadline.get_or_insert_with(|| Instant::now() + GRACE);
+            match tokio::time::timeout_at(deadline, lines.next_line()).await {
+                Ok(line) => line?,
+                Err(_) => None,
+            }
+        } else {
+            tokio::select! {
+                line = lines.next_line() => line?,
+                _ = closing.changed() => continue,
+            }
+        };
+        let line = match line {
+            Some(line) => line,
+            None => return Ok(()),
+        };
+        let (reply, end) = respond(&mut session, &mut is_json, &line);
+        if let Some(recorder) = &mut recorder {
+            recorder.record(&session, Some(&line), reply.as_deref())?;
+        }
+        if let Some(reply) = reply {
+            writer.write_all(format!("{}\n", reply).as_bytes()).await?;
+        }
+        if end {
+            return Ok(());
+        }
+    }
+}
+
+/// The reply to `line` in the text protocol, or the JSON one from a hello on,
+/// and whether the session is over.
+fn respond(session: &mut Session, is_json: &mut bool, line: &str) -> (Option<String>, bool) {
+    match json::hello(line) {
+        Some(json::VERSION) => *is_json = true,
+        // refused in JSON, but the connection keeps to the text protocol
+        Some(_) if !*is_json => return (Some(json::handle(session, line)), false),
+        _ => {}
+    }
+    if *is_json {
+        (Some(json::handle(session, line)), false)
+    } else {
+        text::handle(session, line)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::{
+        io::{AsyncBufReadExt, BufReader},
+        net::TcpStream,
+        sync::oneshot,
+    };
+
+    #[tokio::test]
+    async fn test_sessions() {
+        let server = Server::with_history(
+            toml::from_str("[data]\n[server]\nmax_connections = 2").unwrap(),
+            Some(
+                NaiveDate::from_ymd(2021, 1, 1)
+                    .iter_days()
+                    .take(3)
+                    .map(|date| (date, 1.0))
+                    .collect(),
+            ),
+        );
+        let listener = tokio::net::TcpListener::bind(("127.0.0.1", 0))
+            .await
+            .unwrap();
+        let address = listener.local_addr().unwrap();
+        let (shutdown, signal) = oneshot::channel::<()>();
+        let handle = tokio::spawn(Arc::new(server).serve(listener.into(), async {
+            signal.await.ok();
+        }));
+        let connect = || async {
+            let (reader, writer) = TcpStream::connect(address).await.unwrap().into_split();
+            (BufReader::new(reader).lines(), writer)
+        };
+        let (mut a, mut a_writer) = connect().await;
+        let (mut b, mut b_writer) = connect().await;
+        assert_eq!(a.next_line().await.unwrap().unwrap(), "+2021-01-01 1");
+        assert_eq!(b.next_line().await.unwrap().unwrap(), "+2021-01-01 1");
+        a_writer.write_all(b"i100\n").await.unwrap();
+        assert_eq!(a.next_line().await.unwrap().unwrap(), "+2021-01-02 1");
+        b_writer.write_all(b"r50\n").await.unwrap();
+        assert_eq!(b.next_line().await.unwrap().unwrap(), "-Insufficient");
+        a_writer.write_all(b"r50\n").await.unwrap();
+        assert_eq!(a.next_line().await.unwrap().unwrap(), "+2021-01-03 1");
+        shutdown.send(()).unwrap();
+        assert!(handle.await.unwrap().is_ok());
+        assert_eq!(b.next_line().await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_limit() {
+        let server = Server::with_history(
+            toml::from_str("[data]\n[server]\nmax_connections = 1").unwrap(),
+            Some(
+                NaiveDate::from_ymd(2021, 1, 1)
+                    .iter_days()
+                    .take(3)
+                    .map(|date| (date, 1.0))
+                    .collect(),
+            ),
+        );
+        let listener = tokio::net::TcpListener::bind(("127.0.0.1", 0))
+            .await
+            .unwrap();
+        let address = listener.local_addr().unwrap();
+        let (shutdown, signal) = oneshot::channel::<()>();
+        let handle = tokio::spawn(Arc::new(server).serve(listener.into(), async {
+            signal.await.ok();
+        }));
+        let connect = || async {
+            let (reader, writer) = TcpStream::connect(address).await.unwrap().into_split();
+            (BufReader::new(reader).lines(), writer)
+        };
+        let (mut a, mut a_writer) = connect().await;
+        assert_eq!(a.next_line().await.unwrap().unwrap(), "+2021-01-01 1");
+        let (mut b, mut b_writer) = connect().await;
+        // b waits for a to end
+        assert!(
+            tokio::time::timeout(Duration::from_millis(200), b.next_line())
+                .await
+                .is_err()
+        );
+        a_writer.write_all(b"e\n").await.unwrap();
+        assert_eq!(a.next_line().await.unwrap(), None);
+        assert_eq!(b.next_line().await.unwrap().unwrap(), "+2021-01-01 1");
+
+        shutdown.send(()).unwrap();
+        // the command in flight is still answered before the connection ends
+        b_writer.write_all(b"i100\n").await.unwrap();
+        assert_eq!(b.next_line().await.unwrap().unwrap(), "+2021-01-02 1");
+        assert_eq!(b.next_line().await.unwrap(), None);
+        assert!(handle.await.unwrap().is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_grace() {
+        let server = Server::with_history(
+            toml::from_str("[data]").unwrap(),
+            Some(vec![(NaiveDate::from_ymd(2021, 1, 1), 1.0)]),
+        );
+        let listener = tokio::net::TcpListener::bind(("127.0.0.1", 0))
+            .await
+            .unwrap();
+        let address = listener.local_addr().unwrap();
+        let (shutdown, signal) = oneshot::channel::<()>();
+        let handle = tokio::spawn(Arc::new(server).serve(listener.into(), async {
+            signal.await.ok();
+        }));
+        let (reader, mut writer) = TcpStream::connect(address).await.unwrap().into_split();
+        let mut lines = BufReader::new(reader).lines();
+        assert_eq!(lines.next_line().await.unwrap().unwrap(), "+2021-01-01 1");
+        shutdown.send(()).unwrap();
+        // a client that never stops sending is still closed after `GRACE`
+        let sending = async {
+            while writer.write_all(b"x\n").await.is_ok() {
+                match lines.next_line().await {
+                    Ok(Some(line)) => assert_eq!(line, "-Invalid"),
+                    _ => return,
+                }
+                tokio::time::sleep(Duration::from_millis(200)).await;
+            }
+        };
+        assert!(tokio::time::timeout(GRACE * 3, sending).await.is_ok());
+        assert!(handle.await.unwrap().is_ok());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_unix() {
+        let directory = std::env::temp_dir().join(format!("fund-unix-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let serve: ServeConfig = toml::from_str(&format!(
+            "port = 0\nhttp_port = 0\nunix = {:?}\nannounce = {:?}",
+            directory.join("fund.sock"),
+            directory.join("addresses.txt"),
+        ))
+        .unwrap();
+        let listener = serve.listen.bind().await.unwrap();
+        let http = serve.bind_http().await.unwrap().unwrap();
+        serve
+            .listen
+            .announce(std::io::sink(), &[("line", &listener), ("http", &http)])
+            .unwrap();
+        let addresses = fs::read_to_string(directory.join("addresses.txt")).unwrap();
+        let addresses: Vec<_> = addresses.lines().collect();
+        assert_eq!(
+            addresses[0],
+            format!("line unix:{}", directory.join("fund.sock").display())
+        );
+        assert!(addresses[1].starts_with("http 127.0.0.1:"));
+        assert!(!addresses[1].ends_with(":0"));
+
+        let server = Server::with_history(
+            toml::from_str("[data]").unwrap(),
+            Some(vec![(NaiveDate::from_ymd(2021, 1, 1), 1.0)]),
+        );
+        let (shutdown, signal) = oneshot::channel::<()>();
+        let handle = tokio::spawn(Arc::new(server).serve(listener, async {
+            signal.await.ok();
+        }));
+        let stream = tokio::net::UnixStream::connect(directory.join("fund.sock"))
+            .await
+            .unwrap();
+        let mut lines = BufReader::new(stream).lines();
+        assert_eq!(lines.next_line().await.unwrap().unwrap(), "+2021-01-01 1");
+        shutdown.send(()).unwrap();
+        assert!(handle.await.unwrap().is_ok());
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn test_load() {
+        let directory = std::env::temp_dir().join(format!("fund-load-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        fs::write(
+            directory.join("fund.toml"),
+            "[data]\npath = \"a.csv\"\n[server]\nrecord = \"logs\"",
+        )
+        .unwrap();
+        let config = ServerConfig::load(directory.join(".").join("fund.toml")).unwrap();
+        let base = fs::canonicalize(&directory).unwrap();
+        assert_eq!(config.data.path, Some(base.join("a.csv")));
+        assert_eq!(config.server.record, Some(base.join("logs")));
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn test_respond() {
+        let mut session = Session::new(
+            Repository::new(
+                Box::new(|_| 0.0),
+                NaiveDate::from_ymd(2021, 1, 1)
+                    .iter_days()
+                    .take(3)
+                    .map(|date| (date, 1.0))
+                    .collect(),
+            )
+            .unwrap(),
+        );
+        let mut is_json = false;
+        let (reply, end) = respond(
+            &mut session,
+            &mut is_json,
+            r#"{"id": 1, "type": "hello", "version": 2}"#,
+        );
+        let reply: serde_json::Value = serde_json::from_str(&reply.unwrap()).unwrap();
+        assert_eq!(reply["error"]["code"], "unsupported_version");
+        assert!(!end && !is_json);
+        assert_eq!(
+            respond(&mut session, &mut is_json, "p"),
+            (Some(String::from("+2021-01-02 1")), false)
+        );
+        let (reply, _) = respond(
+            &mut session,
+            &mut is_json,
+            r#"{"id": 2, "type": "hello", "version": 1}"#,
+        );
+        assert!(reply.unwrap().contains(r#""result""#));
+        assert!(is_json);
+    }
+}
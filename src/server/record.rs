@@ -0,0 +1,285 @@
+//! Logs of the connections of a server with `record` set, one JSON line for
+//! its configuration and the hashes of its data files, then one for the
+//! greeting and each request with its response, e.g.
+//!
+//! ```text
+//! {"config": {"data": {"path": "/srv/fund/110022.csv", ...}, ...}, "files": {"/srv/fund/110022.csv": "9c0f..."}}
+//! {"request": null, "response": "+2021-01-01 1"}
+//! {"request": "i100", "response": "+2021-01-04 1.01"}
+//! {"request": "e", "response": null}
+//! ```
+//!
+//! A request that opens a fund also logs the hash of its file. `replay` runs a
+//! log again over a fresh session, once the files it names are found
+//! unchanged.
+
+use super::{respond, text, Server, ServerConfig, Session};
+use crate::error::{Error, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+/// The hashes of files by path, see `hash`.
+type Files = BTreeMap<PathBuf, String>;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Header {
+    config: ServerConfig,
+    #[serde(default)]
+    files: Files,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Entry {
+    /// None for the greeting.
+    pub request: Option<String>,
+    pub response: Option<String>,
+    /// The file of a fund opened by the request.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub files: Files,
+}
+
+pub struct Recorder {
+    writer: BufWriter<File>,
+    source: Option<PathBuf>,
+}
+
+impl Recorder {
+    pub fn create<P: AsRef<Path>>(path: P, config: &ServerConfig) -> Result<Self> {
+        let mut recorder = Self {
+            writer: BufWriter::new(File::create(path)?),
+            source: None,
+        };
+        let mut files = Files::new();
+        if let Some(path) = &config.data.path {
+            files.insert(path.clone(), hash(path)?);
+        }
+        recorder.write(&Header {
+            config: config.clone(),
+            files,
+        })?;
+        Ok(recorder)
+    }
+
+    /// Logs `request` and `response`, with the file of the fund `session`
+    /// opened if it changed.
+    pub fn record(
+        &mut self,
+        session: &Session,
+        request: Option<&str>,
+        response: Option<&str>,
+    ) -> Result<()> {
+        let mut files = Files::new();
+        if session.source() != self.source.as_deref() {
+            self.source = session.source().map(Path::to_path_buf);
+            if let Some(path) = &self.source {
+                files.insert(path.clone(), hash(path)?);
+            }
+        }
+        self.write(&Entry {
+            request: request.map(String::from),
+            response: response.map(String::from),
+            files,
+        })
+    }
+
+    /// Writes `line` through, so that the log survives a crash.
+    fn write<T: Serialize>(&mut self, line: &T) -> Result<()> {
+        serde_json::to_writer(&mut self.writer, line)?;
+        writeln!(&mut self.writer)?;
+        Ok(self.writer.flush()?)
+    }
+}
+
+/// The FNV-1a hash of the file at `path`, in hex.
+fn hash(path: &Path) -> Result<String> {
+    let hash = fs::read(path)?
+        .iter()
+        .fold(0xcbf2_9ce4_8422_2325_u64, |hash, &byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+        });
+    Ok(format!("{:016x}", hash))
+}
+
+/// The first of `files` missing or changed since it was logged.
+fn changed(files: &Files) -> Option<PathBuf> {
+    files
+        .iter()
+        .find(|(path, expected)| hash(path).ok().as_ref() != Some(expected))
+        .map(|(path, _)| path.clone())
+}
+
+/// The first entry of a log answered differently on replay.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Divergence {
+    /// The line of the entry in the log, starting from 1.
+    pub line: usize,
+    pub request: Option<String>,
+    pub expected: Option<String>,
+    pub actual: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Outcome {
+    /// Every entry was answered as logged.
+    Replayed,
+    /// A data file differs from the one logged, so the log was not replayed
+    /// from `line` on.
+    Changed {
+        line: usize,
+        path: PathBuf,
+    },
+    Diverged(Divergence),
+}
+
+/// Replays the log at `path` over a fresh session of its configuration.
+pub fn replay<P: AsRef<Path>>(path: P) -> Result<Outcome> {
+    let mut lines = BufReader::new(File::open(path)?).lines();
+    let header: Header = serde_json::from_str(&lines.next().ok_or(Error::Invalid)??)?;
+    if let Some(path) = changed(&header.files) {
+        return Ok(Outcome::Changed { line: 1, path });
+    }
+    let mut session = Server::new(header.config)?.session()?;
+    let mut is_json = false;
+    for (i, line) in lines.enumerate() {
+        let entry: Entry = serde_json::from_str(&line?)?;
+        if let Some(path) = changed(&entry.files) {
+            return Ok(Outcome::Changed { line: i + 2, path });
+        }
+        let actual = match (i, &entry.request) {
+            (0, None) => Some(text::greeting(&session)),
+            (0, _) | (_, None) => return Err(Error::Invalid),
+            (_, Some(request)) => respond(&mut session, &mut is_json, request).0,
+        };
+        if actual != entry.response {
+            return Ok(Outcome::Diverged(Divergence {
+                line: i + 2,
+                request: entry.request,
+                expected: entry.response,
+                actual,
+            }));
+        }
+    }
+    Ok(Outcome::Replayed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_replay() {
+        let directory = std::env::temp_dir().join(format!("fund-record-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        fs::write(
+            directory.join("a.csv"),
+            "date,nav\n2021-01-01,1.0\n2021-01-02,1.1\n2021-01-03,1.2\n",
+        )
+        .unwrap();
+        let config: ServerConfig = toml::from_str(&format!(
+            "[data]\npath = {:?}\n[cash]\ninitial = 100.0",
+            directory.join("a.csv")
+        ))
+        .unwrap();
+        let session = Session::default();
+        let path = directory.join("0.jsonl");
+        let mut recorder = Recorder::create(&path, &config).unwrap();
+        recorder
+            .record(&session, None, Some("+2021-01-01 1"))
+            .unwrap();
+        recorder
+            .record(&session, Some("i100"), Some("+2021-01-02 1.1"))
+            .unwrap();
+        recorder
+            .record(
+                &session,
+                Some(r#"{"id": 1, "type": "hello", "version": 1}"#),
+                Some(r#"{"id":1,"result":{"type":"hello","version":1,"next":{"date":"2021-01-02","net_asset_value":1.1}}}"#),
+            )
+            .unwrap();
+        recorder
+            .record(
+                &session,
+                Some(r#"{"id": 2, "type": "cash"}"#),
+                Some(r#"{"id":2,"result":{"type":"cash","cash":0.0}}"#),
+            )
+            .unwrap();
+        assert_eq!(replay(&path).unwrap(), Outcome::Replayed);
+
+        recorder
+            .record(
+                &session,
+                Some(r#"{"id": 3, "type": "invest", "investment": 1.0}"#),
+                Some(r#"{"id":3,"result":{"type":"confirmed"}}"#),
+            )
+            .unwrap();
+        let divergence = match replay(&path).unwrap() {
+            Outcome::Diverged(divergence) => divergence,
+            outcome => panic!("{:?}", outcome),
+        };
+        assert_eq!(divergence.line, 6);
+        assert_eq!(
+            divergence.actual.unwrap(),
+            r#"{"id":3,"error":{"code":"insufficient","message":"Insufficient"}}"#
+        );
+
+        fs::write(directory.join("a.csv"), "date,nav\n2021-01-01,1.0\n").unwrap();
+        assert_eq!(
+            replay(&path).unwrap(),
+            Outcome::Changed {
+                line: 1,
+                path: directory.join("a.csv")
+            }
+        );
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn test_opened() {
+        let directory = std::env::temp_dir().join(format!("fund-opened-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        fs::write(
+            directory.join("b.csv"),
+            "date,nav\n2021-01-01,1.0\n2021-01-02,1.1\n",
+        )
+        .unwrap();
+        let config: ServerConfig =
+            toml::from_str(&format!("[funds]\ndirectory = {:?}", directory)).unwrap();
+        let path = directory.join("0.jsonl");
+        let mut recorder = Recorder::create(&path, &config).unwrap();
+        let mut session = Session::default().with_directory(directory.clone());
+        recorder
+            .record(&session, None, Some(&text::greeting(&session)))
+            .unwrap();
+        let mut is_json = false;
+        for request in &[
+            r#"{"id": 1, "type": "hello", "version": 1}"#,
+            r#"{"id": 2, "type": "open", "fund": "b"}"#,
+            r#"{"id": 3, "type": "pass"}"#,
+        ] {
+            let (response, _) = respond(&mut session, &mut is_json, request);
+            recorder
+                .record(&session, Some(request), response.as_deref())
+                .unwrap();
+        }
+        let lines = fs::read_to_string(&path).unwrap();
+        let lines: Vec<&str> = lines.lines().collect();
+        assert!(lines[3].contains("b.csv") && !lines[4].contains("b.csv"));
+        assert_eq!(replay(&path).unwrap(), Outcome::Replayed);
+
+        fs::write(directory.join("b.csv"), "date,nav\n2021-01-01,1.0\n").unwrap();
+        assert_eq!(
+            replay(&path).unwrap(),
+            Outcome::Changed {
+                line: 4,
+                path: directory.join("b.csv")
+            }
+        );
+        fs::remove_dir_all(directory).unwrap();
+    }
+}
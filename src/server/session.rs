@@ -0,0 +1,289 @@
+use crate::{
+    backtest::Action,
+    config::{RuleConfig, ScheduleSource},
+    data,
+    error::{Error, Result},
+    repository::Repository,
+};
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// The most days a batch of `apply` may span, as they are undone on failure,
+/// about a year of trading days.
+const UNDO: usize = 250;
+
+/// The simulation of one client, independent of the protocol it speaks.
+#[derive(Debug, Default)]
+pub struct Session {
+    repository: Option<Repository>,
+    directory: Option<PathBuf>,
+    /// The file of the fund opened last, if any.
+    source: Option<PathBuf>,
+}
+
+/// A session over a fund of the server's directory, e.g.
+///
+/// ```json
+/// {"fund": "110022", "start": "2020-01-01", "cash": 100000.0, "rule": {"redemption_rates": [[7, 0.015]]}}
+/// ```
+///
+/// The rule takes the fields of `config::RuleConfig`, with its schedule inline.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Setup {
+    pub fund: String,
+    pub start: Option<NaiveDate>,
+    pub end: Option<NaiveDate>,
+    #[serde(default)]
+    pub warm_up: usize,
+    /// Unlimited if absent.
+    pub cash: Option<f64>,
+    #[serde(default)]
+    pub rule: RuleConfig,
+}
+
+impl Session {
+    pub fn new(repository: Repository) -> Self {
+        Self {
+            repository: Some(repository.with_undo(UNDO)),
+            directory: None,
+            source: None,
+        }
+    }
+
+    /// Lets the client open the funds of `directory`, one `<fund>.csv` each.
+    pub fn with_directory(mut self, directory: PathBuf) -> Self {
+        self.directory = Some(directory);
+        self
+    }
+
+    /// Fails with `Error::Invalid` until a fund is opened.
+    pub fn repository(&self) -> Result<&Repository> {
+        self.repository.as_ref().ok_or(Error::Invalid)
+    }
+
+    fn repository_mut(&mut self) -> Result<&mut Repository> {
+        self.repository.as_mut().ok_or(Error::Invalid)
+    }
+
+    pub fn into_repository(self) -> Option<Repository> {
+        self.repository
+    }
+
+    /// The file of the fund opened last, for `record`.
+    pub fn source(&self) -> Option<&Path> {
+        self.source.as_deref()
+    }
+
+    /// Replaces the simulation with a fresh one as described by `setup`.
+    pub fn open(&mut self, setup: &Setup) -> Result<()> {
+        let directory = self.directory.as_ref().ok_or(Error::Invalid)?;
+        if Path::new(&setup.fund).file_name() != Some(setup.fund.as_ref()) {
+            return Err(Error::Invalid);
+        }
+        if let Some(ScheduleSource::Path(_)) = setup.rule.schedule {
+            return Err(Error::Invalid);
+        }
+        let source = directory.join(format!("{}.csv", setup.fund));
+        let history = data::load(&source)?;
+        let repository = Repository::new(setup.rule.build()?, history)?.with_range(
+            setup.start,
+            setup.end,
+            setup.warm_up,
+        )?;
+        let repository = repository.with_undo(UNDO);
+        self.repository = Some(match setup.cash {
+            Some(cash) => repository.with_cash(cash),
+            None => repository,
+        });
+        self.source = Some(source);
+        Ok(())
+    }
+
+    /// Starts the simulation over.
+    pub fn reset(&mut self) -> Result<()> {
+        self.repository_mut()?.restart()
+    }
+
+    pub fn check(&self) -> Result<(NaiveDate, f64)> {
+        self.repository()?.check()
+    }
+
+    pub fn pass(&mut self) -> Result<()> {
+        self.repository_mut()?.pass()
+    }
+
+    pub fn invest(&mut self, investment: f64) -> Result<()> {
+        self.repository_mut()?.invest(investment)
+    }
+
+    pub fn redeem(&mut self, redemption: f64) -> Result<()> {
+        self.repository_mut()?.redeem(redemption)
+    }
+
+    /// Passes up to `days` days, fewer if the data runs out first.
+    pub fn pass_days(&mut self, days: usize) -> Result<()> {
+        if days == 0 {
+            return Err(Error::Invalid);
+        }
+        let repository = self.repository_mut()?;
+        repository.pass()?;
+        for _ in 1..days {
+            if repository.pass().is_err() {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// Passes the days before `date`, if any.
+    pub fn pass_until(&mut self, date: NaiveDate) -> Result<()> {
+        let repository = self.repository_mut()?;
+        repository.check()?;
+        while let Ok((day, _)) = repository.check() {
+            if day >= date {
+                break;
+            }
+            repository.pass()?;
+        }
+        Ok(())
+    }
+
+    /// Carries out `actions` on consecutive days, all of them or none, so
+    /// that a batch may not span more days than the repository can undo.
+    pub fn apply(&mut self, actions: &[Action]) -> Result<()> {
+        let repository = self.repository_mut()?;
+        if actions.len() > repository.undo_depth() + 1 {
+            return Err(Error::Invalid);
+        }
+        for (i, action) in actions.iter().enumerate() {
+            let res = match *action {
+                Action::Pass => repository.pass(),
+                Action::Invest(investment) => repository.invest(investment),
+                Action::Redeem(redemption) => repository.redeem(redemption),
+            };
+            if let Err(err) = res {
+                for _ in 0..i {
+                    repository.undo()?;
+                }
+                return Err(err);
+            }
+        }
+        Ok(())
+    }
+
+    /// Passes the remaining days.
+    pub fn finish(&mut self) -> Result<()> {
+        let repository = self.repository_mut()?;
+        while repository.pass().is_ok() {}
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_open() {
+        let directory = std::env::temp_dir().join(format!("fund-session-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        fs::write(
+            directory.join("a.csv"),
+            "date,nav\n2021-01-01,1.0\n2021-01-02,1.1\n2021-01-03,1.2\n",
+        )
+        .unwrap();
+        let mut session = Session::default().with_directory(directory.clone());
+        assert!(if let Err(Error::Invalid) = session.pass() {
+            true
+        } else {
+            false
+        });
+        let mut setup: Setup = serde_json::from_str(
+            r#"{"fund": "a", "start": "2021-01-02", "cash": 100.0, "rule": {"redemption_rates": [[7, 0.015]]}}"#,
+        )
+        .unwrap();
+        assert!(session.open(&setup).is_ok());
+        assert_eq!(
+            session.check().unwrap(),
+            (NaiveDate::from_ymd(2021, 1, 2), 1.1)
+        );
+        assert!(session.invest(100.0).is_ok());
+        assert!(if let Err(Error::Insufficient) = session.invest(1.0) {
+            true
+        } else {
+            false
+        });
+        assert!(session.reset().is_ok());
+        assert!(session.repository().unwrap().transactions().is_empty());
+        assert!(session.invest(100.0).is_ok());
+
+        setup.fund = String::from("../a");
+        assert!(if let Err(Error::Invalid) = session.open(&setup) {
+            true
+        } else {
+            false
+        });
+        setup.fund = String::from("a");
+        setup.rule.redemption_rates.clear();
+        setup.rule.schedule = Some(ScheduleSource::Path(PathBuf::from("/etc/passwd")));
+        assert!(if let Err(Error::Invalid) = session.open(&setup) {
+            true
+        } else {
+            false
+        });
+        assert_eq!(session.repository().unwrap().transactions().len(), 1);
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn test_fast_forward() {
+        let mut session = Session::new(
+            Repository::new(
+                Box::new(|_| 0.0),
+                NaiveDate::from_ymd(2021, 1, 1)
+                    .iter_days()
+                    .take(10)
+                    .map(|date| (date, 1.0))
+                    .collect(),
+            )
+            .unwrap()
+            .with_cash(100.0),
+        );
+        assert!(session.pass_days(3).is_ok());
+        assert_eq!(session.check().unwrap().0, NaiveDate::from_ymd(2021, 1, 4));
+        assert!(session.pass_until(NaiveDate::from_ymd(2021, 1, 6)).is_ok());
+        assert_eq!(session.check().unwrap().0, NaiveDate::from_ymd(2021, 1, 6));
+        assert!(session.pass_until(NaiveDate::from_ymd(2021, 1, 1)).is_ok());
+        assert_eq!(session.check().unwrap().0, NaiveDate::from_ymd(2021, 1, 6));
+
+        assert!(if let Err(Error::Insufficient) =
+            session.apply(&[Action::Invest(60.0), Action::Pass, Action::Invest(60.0)])
+        {
+            true
+        } else {
+            false
+        });
+        assert_eq!(session.check().unwrap().0, NaiveDate::from_ymd(2021, 1, 6));
+        assert!(session.repository().unwrap().transactions().is_empty());
+        assert!(session
+            .apply(&[Action::Invest(60.0), Action::Pass, Action::Redeem(10.0)])
+            .is_ok());
+        assert_eq!(session.repository().unwrap().transactions().len(), 2);
+
+        assert!(
+            if let Err(Error::Invalid) = session.apply(&vec![Action::Pass; UNDO + 2]) {
+                true
+            } else {
+                false
+            }
+        );
+        assert!(session.pass_days(5).is_ok());
+        assert!(if let Err(Error::Overflow) = session.pass_days(1) {
+            true
+        } else {
+            false
+        });
+    }
+}
@@ -0,0 +1,115 @@
+//! The line protocol: `p` passes, `i<investment>` invests, `r<redemption>`
+//! redeems and `e` passes the remaining days and ends the session. Each order
+//! is answered with the next `+<date> <net asset value>` or `-<error>`.
+//!
+//! `p<days>` passes that many days and `u<date>` the days before that date.
+//! Orders for consecutive days may be sent at once separated by `;`, e.g.
+//! `i1000;p;p;r500`, and are carried out all or none, with one answer.
+
+use super::session::Session;
+use crate::{
+    backtest::Action,
+    error::{Error, Result},
+};
+use chrono::NaiveDate;
+use lazy_static::lazy_static;
+use regex::Regex;
+
+pub fn greeting(session: &Session) -> String {
+    match session.check() {
+        Ok((date, nav)) => format!("+{} {}", date, nav),
+        Err(err) => format!("-{}", err),
+    }
+}
+
+/// The reply to `line`, if any, and whether the session is over.
+pub fn handle(session: &mut Session, line: &str) -> (Option<String>, bool) {
+    if line == "e" {
+        session.finish().ok();
+        (None, true)
+    } else if let Some(res) = pass(session, line)
+        .or_else(|| invest(session, line))
+        .or_else(|| redeem(session, line))
+        .or_else(|| pass_until(session, line))
+        .or_else(|| batch(session, line))
+    {
+        match res {
+            Ok(()) => match session.check() {
+                Ok((date, nav)) => (Some(format!("+{} {}", date, nav)), false),
+                Err(err) => (Some(format!("-{}", err)), true),
+            },
+            Err(err) => (Some(format!("-{}", err)), false),
+        }
+    } else {
+        (Some(String::from("-Invalid")), false)
+    }
+}
+
+fn pass(session: &mut Session, line: &str) -> Option<Result<()>> {
+    lazy_static! {
+        static ref RE: Regex = Regex::new(r"^p(\d*)$").unwrap();
+    }
+    RE.captures(line).map(|caps| match &caps[1] {
+        "" => session.pass(),
+        days => days
+            .parse()
+            .map_err(|_| Error::Invalid)
+            .and_then(|days| session.pass_days(days)),
+    })
+}
+
+fn pass_until(session: &mut Session, line: &str) -> Option<Result<()>> {
+    lazy_static! {
+        static ref RE: Regex = Regex::new(r"^u(\S+)$").unwrap();
+    }
+    RE.captures(line)
+        .and_then(|caps| {
+            caps.get(1)
+                .and_then(|x| x.as_str().parse::<NaiveDate>().ok())
+        })
+        .map(|date| session.pass_until(date))
+}
+
+fn batch(session: &mut Session, line: &str) -> Option<Result<()>> {
+    if !line.contains(';') {
+        return None;
+    }
+    let actions = line
+        .split(';')
+        .map(|order| match (order.get(..1), order.get(1..)) {
+            (Some("p"), Some("")) => Some(Action::Pass),
+            (Some("i"), Some(investment)) => investment.parse().ok().map(Action::Invest),
+            (Some("r"), Some(redemption)) => redemption.parse().ok().map(Action::Redeem),
+            _ => None,
+        })
+        .collect::<Option<Vec<_>>>()?;
+    Some(session.apply(&actions))
+}
+
+fn invest(session: &mut Session, line: &str) -> Option<Result<()>> {
+    lazy_static! {
+        static ref RE: Regex = Regex::new(r"^i(\S+)$").unwrap();
+    }
+    if let Some(investment) = RE
+        .captures(line)
+        .and_then(|caps| caps.get(1).and_then(|x| x.as_str().parse().ok()))
+    {
+        Some(session.invest(investment))
+    } else {
+        None
+    }
+}
+
+fn redeem(session: &mut Session, line: &str) -> Option<Result<()>> {
+    lazy_static! {
+        static ref RE: Regex = Regex::new(r"^r(\S+)$").unwrap();
+    }
+    if let Some(redemption) = RE
+        .captures(line)
+        .and_then(|caps| caps.get(1).and_then(|x| x.as_str().parse().ok()))
+    {
+        Some(session.redeem(redemption))
+    } else {
+        None
+    }
+}
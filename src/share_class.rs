@@ -0,0 +1,252 @@
+use crate::{
+    config::{Config, RuleConfig},
+    data,
+    error::{Error, Result},
+    metrics::Metrics,
+    repository::{Order, Repository, Transaction},
+};
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// The two classes of a fund, e.g.
+///
+/// ```toml
+/// [share_classes]
+/// amount = 10000.0
+///
+/// [share_classes.a]
+/// path = "110022.csv"
+/// investment_rates = [[1000000.0, 0.015]]
+/// redemption_rates = [[7, 0.015], [365, 0.005], [730, 0.0025]]
+///
+/// [share_classes.c]
+/// path = "010022.csv"
+/// redemption_rates = [[7, 0.015], [30, 0.005]]
+/// ```
+///
+/// `amount` is the investment used to find the break-even holding period.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShareClassConfig {
+    pub amount: f64,
+    pub a: ClassConfig,
+    pub c: ClassConfig,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClassConfig {
+    pub path: PathBuf,
+    #[serde(flatten)]
+    pub rule: RuleConfig,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Class {
+    A,
+    C,
+}
+
+/// The money received when a single investment is redeemed after `days`.
+#[derive(Debug, Serialize)]
+pub struct Holding {
+    pub date: NaiveDate,
+    pub days: i64,
+    pub a: f64,
+    pub c: f64,
+}
+
+/// An investment of the strategy held until the end in either class.
+#[derive(Debug, Serialize)]
+pub struct Lot {
+    pub date: NaiveDate,
+    pub investment: f64,
+    pub days: i64,
+    pub a: f64,
+    pub c: f64,
+    pub winner: Class,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Comparison {
+    pub a: Metrics,
+    pub c: Metrics,
+    /// The holding period from which class A always returns at least as much
+    /// as class C, if it does at the end.
+    pub break_even: Option<i64>,
+    pub holdings: Vec<Holding>,
+    pub lots: Vec<Lot>,
+}
+
+impl Comparison {
+    pub fn new(config: &Config, share_classes: &ShareClassConfig) -> Result<Self> {
+        let a_history = data::load(&share_classes.a.path)?;
+        let c_history = data::load(&share_classes.c.path)?;
+        let run = |class: &ClassConfig, history: &[(NaiveDate, f64)]| {
+            let mut config = config.clone();
+            config.rule = class.rule.clone();
+            config.run(history.to_vec())
+        };
+        let a_repository = run(&share_classes.a, &a_history)?;
+        let c_repository = run(&share_classes.c, &c_history)?;
+        if a_repository.net_asset_value_history().len()
+            != c_repository.net_asset_value_history().len()
+            || a_repository
+                .net_asset_value_history()
+                .iter()
+                .zip(c_repository.net_asset_value_history())
+                .any(|(a, c)| a.0 != c.0)
+        {
+            return Err(Error::Invalid);
+        }
+        let hold_both = |start: NaiveDate, investment: f64| -> Result<Vec<(NaiveDate, f64, f64)>> {
+            let a = hold(config, &share_classes.a.rule, &a_history, start, investment)?;
+            let c = hold(config, &share_classes.c.rule, &c_history, start, investment)?;
+            Ok(a.into_iter()
+                .zip(c)
+                .map(|((date, a), (_, c))| (date, a, c))
+                .collect())
+        };
+        let start = a_repository.net_asset_value_history()[0].0;
+        let holdings: Vec<Holding> = hold_both(start, share_classes.amount)?
+            .into_iter()
+            .map(|(date, a, c)| Holding {
+                date,
+                days: (date - start).num_days(),
+                a,
+                c,
+            })
+            .collect();
+        let break_even = match holdings.iter().rposition(|holding| holding.a < holding.c) {
+            Some(i) => holdings.get(i + 1).map(|holding| holding.days),
+            None => holdings.first().map(|holding| holding.days),
+        };
+        let mut lots = vec![];
+        for transaction in a_repository.transactions() {
+            if let Transaction::Investment {
+                date, investment, ..
+            } = *transaction
+            {
+                if let Some(&(end, a, c)) = hold_both(date, investment)?.last() {
+                    lots.push(Lot {
+                        date,
+                        investment,
+                        days: (end - date).num_days(),
+                        a,
+                        c,
+                        winner: if a >= c { Class::A } else { Class::C },
+                    });
+                }
+            }
+        }
+        Ok(Self {
+            a: Metrics::new(&a_repository),
+            c: Metrics::new(&c_repository),
+            break_even,
+            holdings,
+            lots,
+        })
+    }
+}
+
+/// The money received on each record from `start` to the end of the range if
+/// `investment` made on `start` were redeemed in full that day, quoted on a
+/// single repository.
+fn hold(
+    config: &Config,
+    rule: &RuleConfig,
+    history: &[(NaiveDate, f64)],
+    start: NaiveDate,
+    investment: f64,
+) -> Result<Vec<(NaiveDate, f64)>> {
+    let mut repository = Repository::new(rule.build()?, history.to_vec())?.with_range(
+        Some(start),
+        config.data.end,
+        0,
+    )?;
+    repository.invest(investment)?;
+    let mut values = vec![];
+    loop {
+        let days = repository.daily_infos().len();
+        let (date, net_asset_value) = repository.net_asset_value_history()[days - 1];
+        let redemption = repository.daily_infos()[days - 1].holding_share();
+        let fee = repository
+            .rule()
+            .quote(Order::Redemption {
+                date,
+                net_asset_value,
+                redemption,
+            })
+            .ok_or(Error::Invalid)?;
+        values.push((date, redemption * net_asset_value - fee));
+        if repository.pass().is_err() {
+            return Ok(values);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_comparison() {
+        let directory =
+            std::env::temp_dir().join(format!("fund-share-class-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let mut csv = String::from("date,nav\n");
+        for date in NaiveDate::from_ymd(2021, 1, 1).iter_days().take(10) {
+            csv.push_str(&format!("{},1.0\n", date));
+        }
+        fs::write(directory.join("a.csv"), &csv).unwrap();
+        fs::write(directory.join("c.csv"), &csv).unwrap();
+        let config: Config = toml::from_str(&format!(
+            r#"
+            [data]
+            path = {:?}
+
+            [strategy]
+            type = "dca"
+            amount = 10000.0
+            interval = 20
+
+            [share_classes]
+            amount = 10000.0
+
+            [share_classes.a]
+            path = {:?}
+            investment_rates = [[1000000.0, 0.01]]
+            redemption_rates = [[3, 0.01]]
+
+            [share_classes.c]
+            path = {:?}
+            redemption_rates = [[7, 0.015]]
+            sales_service = 0.73
+            "#,
+            directory.join("a.csv"),
+            directory.join("a.csv"),
+            directory.join("c.csv"),
+        ))
+        .unwrap();
+        let comparison = Comparison::new(&config, config.share_classes.as_ref().unwrap()).unwrap();
+        // A pays 1% up front and 1% within 3 days, C 0.2% a day and 1.5%
+        // within 7 days.
+        assert_eq!(comparison.holdings.len(), 10);
+        for holding in &comparison.holdings {
+            let a = if holding.days < 3 { 9801.0 } else { 9900.0 };
+            let c = 10000.0
+                * 0.998f64.powi(holding.days as i32 + 1)
+                * if holding.days < 7 { 0.985 } else { 1.0 };
+            assert!((holding.a - a).abs() < 1e-6);
+            assert!((holding.c - c).abs() < 1e-6);
+        }
+        assert_eq!(comparison.break_even, Some(2));
+        assert_eq!(comparison.lots.len(), 1);
+        assert_eq!(comparison.lots[0].days, 9);
+        assert_eq!(comparison.lots[0].winner, Class::A);
+        assert_eq!(comparison.a.investment, 10000.0);
+        assert!((comparison.c.value - 10000.0 * 0.998f64.powi(10)).abs() < 1e-6);
+        fs::remove_dir_all(directory).unwrap();
+    }
+}
@@ -0,0 +1,32 @@
+use crate::{
+    backtest::{Action, Strategy},
+    repository::Repository,
+};
+
+/// Dollar-cost averaging: invests a fixed amount every `interval` trading days
+/// while there is enough cash.
+pub struct Dca {
+    amount: f64,
+    interval: usize,
+}
+
+impl Dca {
+    pub fn new(amount: f64, interval: usize) -> Self {
+        Self {
+            amount,
+            interval: interval.max(1),
+        }
+    }
+}
+
+impl Strategy for Dca {
+    fn decide(&mut self, repository: &Repository) -> Action {
+        if repository.daily_infos().len().is_multiple_of(self.interval)
+            && self.amount <= repository.cash()
+        {
+            Action::Invest(self.amount)
+        } else {
+            Action::Pass
+        }
+    }
+}
@@ -0,0 +1,5 @@
+pub use dca::Dca;
+pub use moving_average::MovingAverage;
+
+mod dca;
+mod moving_average;
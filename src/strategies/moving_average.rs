@@ -0,0 +1,39 @@
+use crate::{
+    backtest::{Action, Strategy},
+    repository::Repository,
+};
+
+/// Dollar-cost averaging that skips a scheduled investment while the net asset
+/// value is above its moving average over `window` records.
+pub struct MovingAverage {
+    amount: f64,
+    interval: usize,
+    window: usize,
+}
+
+impl MovingAverage {
+    pub fn new(amount: f64, interval: usize, window: usize) -> Self {
+        Self {
+            amount,
+            interval: interval.max(1),
+            window: window.max(1),
+        }
+    }
+}
+
+impl Strategy for MovingAverage {
+    fn decide(&mut self, repository: &Repository) -> Action {
+        let window = repository.window(self.window);
+        if !repository.daily_infos().len().is_multiple_of(self.interval)
+            || window.len() < self.window
+            || repository.cash() < self.amount
+        {
+            return Action::Pass;
+        }
+        let average = window.iter().map(|&(_, nav)| nav).sum::<f64>() / window.len() as f64;
+        match window.last() {
+            Some(&(_, nav)) if nav <= average => Action::Invest(self.amount),
+            _ => Action::Pass,
+        }
+    }
+}
@@ -0,0 +1,241 @@
+use crate::{
+    error::Result,
+    repository::{DailyInfo, Repository, Transaction},
+    server::{ListenConfig, Listener, BACKOFF},
+};
+use chrono::NaiveDate;
+use futures_util::SinkExt;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tokio::{
+    sync::{broadcast, mpsc, watch},
+    task::JoinHandle,
+};
+use tokio_tungstenite::tungstenite::Message;
+
+/// Streams the progress of `fund run` over WebSocket, e.g.
+///
+/// ```toml
+/// [stream]
+/// port = 8001
+/// wait = true
+/// ```
+///
+/// listening as `[server]` does, see `server::ListenConfig`. Every subscriber
+/// receives a `Progress` as JSON text for each day simulated after it
+/// connected, and a close frame once the run ends.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StreamConfig {
+    #[serde(flatten)]
+    pub listen: ListenConfig,
+    /// Starts the run only once a subscriber connected.
+    #[serde(default)]
+    pub wait: bool,
+}
+
+/// A simulated day and the transaction made on it, if any.
+#[derive(Debug, Serialize)]
+pub struct Progress<'a> {
+    pub date: NaiveDate,
+    pub net_asset_value: f64,
+    pub daily_info: &'a DailyInfo,
+    pub transaction: Option<&'a Transaction>,
+}
+
+impl<'a> Progress<'a> {
+    /// The last day simulated by `repository`, if any.
+    pub fn new(repository: &'a Repository) -> Option<Self> {
+        let daily_info = repository.daily_infos().last()?;
+        let (date, net_asset_value) =
+            repository.net_asset_value_history()[repository.daily_infos().len() - 1];
+        Some(Self {
+            date,
+            net_asset_value,
+            daily_info,
+            transaction: daily_info
+                .transaction_id()
+                .map(|id| &repository.transactions()[id]),
+        })
+    }
+}
+
+/// How long `Stream::close` waits for the subscribers by default.
+const GRACE: Duration = Duration::from_secs(5);
+
+pub struct Stream {
+    sender: broadcast::Sender<String>,
+    subscribers: watch::Receiver<usize>,
+    closing: watch::Sender<bool>,
+    finished: mpsc::Receiver<()>,
+    /// Stops accepting and returns the tasks of the subscribers.
+    accepting: JoinHandle<Vec<JoinHandle<()>>>,
+    grace: Duration,
+}
+
+impl Stream {
+    /// Accepts subscribers of `listener` in the background, keeping up to
+    /// `capacity` messages for the slow ones, who skip the older ones.
+    ///
+    /// Must be called within a tokio runtime.
+    pub fn new(listener: Listener, capacity: usize) -> Self {
+        let (sender, _) = broadcast::channel(capacity.max(1));
+        let (count, subscribers) = watch::channel(0);
+        let (closing, mut closed) = watch::channel(false);
+        let (done, finished) = mpsc::channel::<()>(1);
+        let subscribe = sender.clone();
+        let accepting = tokio::spawn(async move {
+            let mut tasks = Vec::new();
+            for subscriber in 1.. {
+                let stream = tokio::select! {
+                    accepted = listener.accept() => match accepted {
+                        Ok(stream) => stream,
+                        Err(err) => {
+                            eprintln!("{}", err);
+                            tokio::time::sleep(BACKOFF).await;
+                            continue;
+                        }
+                    },
+                    _ = closed.changed() => break,
+                };
+                let mut receiver = subscribe.subscribe();
+                count.send(subscriber).ok();
+                let done = done.clone();
+                tasks.push(tokio::spawn(async move {
+                    let mut socket = match tokio_tungstenite::accept_async(stream).await {
+                        Ok(socket) => socket,
+                        Err(err) => {
+                            eprintln!("{}", err);
+                            return;
+                        }
+                    };
+                    loop {
+                        let message = match receiver.recv().await {
+                            Ok(progress) => Message::Text(progress),
+                            Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                            Err(broadcast::error::RecvError::Closed) => break,
+                        };
+                        if socket.send(message).await.is_err() {
+                            return;
+                        }
+                    }
+                    socket.close(None).await.ok();
+                    drop(done);
+                }));
+            }
+            tasks
+        });
+        Self {
+            sender,
+            subscribers,
+            closing,
+            finished,
+            accepting,
+            grace: GRACE,
+        }
+    }
+
+    /// Waits up to `grace` on `close` for the subscribers to receive the
+    /// remaining messages.
+    pub fn with_grace(mut self, grace: Duration) -> Self {
+        self.grace = grace;
+        self
+    }
+
+    /// Waits for the first subscriber.
+    pub async fn subscribed(&mut self) {
+        while *self.subscribers.borrow() == 0 {
+            if self.subscribers.changed().await.is_err() {
+                return;
+            }
+        }
+    }
+
+    /// Sends the last day simulated by `repository` to every subscriber.
+    pub fn publish(&self, repository: &Repository) -> Result<()> {
+        if let Some(progress) = Progress::new(repository) {
+            // Fails only without subscribers.
+            self.sender.send(serde_json::to_string(&progress)?).ok();
+        }
+        Ok(())
+    }
+
+    /// Stops accepting subscribers and waits for the current ones to receive
+    /// the remaining messages, then drops those still behind after `grace`.
+    pub async fn close(self) {
+        let Self {
+            sender,
+            closing,
+            mut finished,
+            accepting,
+            grace,
+            ..
+        } = self;
+        closing.send(true).ok();
+        drop(sender);
+        let tasks = accepting.await.unwrap_or_default();
+        tokio::time::timeout(grace, finished.recv()).await.ok();
+        for task in tasks {
+            task.abort();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_util::StreamExt;
+
+    #[tokio::test]
+    async fn test_stream() {
+        let listener = tokio::net::TcpListener::bind(("127.0.0.1", 0))
+            .await
+            .unwrap();
+        let address = listener.local_addr().unwrap();
+        let mut stream = Stream::new(listener.into(), 4);
+        let (mut socket, _) = tokio_tungstenite::connect_async(format!("ws://{}", address))
+            .await
+            .unwrap();
+        stream.subscribed().await;
+        let mut repository = Repository::new(
+            Box::new(|_| 1.0),
+            vec![
+                (NaiveDate::from_ymd(2021, 1, 1), 1.0),
+                (NaiveDate::from_ymd(2021, 1, 2), 1.1),
+            ],
+        )
+        .unwrap();
+        repository.invest(101.0).unwrap();
+        assert!(stream.publish(&repository).is_ok());
+        repository.pass().unwrap();
+        assert!(stream.publish(&repository).is_ok());
+        let closed = tokio::spawn(stream.close());
+
+        let message = socket.next().await.unwrap().unwrap();
+        let progress: serde_json::Value = serde_json::from_str(message.to_text().unwrap()).unwrap();
+        assert_eq!(progress["date"], "2021-01-01");
+        assert_eq!(progress["daily_info"]["holding_share"], 100.0);
+        assert_eq!(progress["transaction"]["type"], "investment");
+        assert_eq!(progress["transaction"]["fee"], 1.0);
+        let message = socket.next().await.unwrap().unwrap();
+        let progress: serde_json::Value = serde_json::from_str(message.to_text().unwrap()).unwrap();
+        assert_eq!(progress["net_asset_value"], 1.1);
+        assert!(progress["transaction"].is_null());
+        assert!(socket.next().await.unwrap().unwrap().is_close());
+        closed.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_stalled() {
+        let listener = tokio::net::TcpListener::bind(("127.0.0.1", 0))
+            .await
+            .unwrap();
+        let address = listener.local_addr().unwrap();
+        let mut stream = Stream::new(listener.into(), 4).with_grace(Duration::from_millis(100));
+        // never completes the handshake
+        let _socket = tokio::net::TcpStream::connect(address).await.unwrap();
+        stream.subscribed().await;
+        assert!(tokio::time::timeout(Duration::from_secs(5), stream.close())
+            .await
+            .is_ok());
+    }
+}
@@ -0,0 +1,253 @@
+use crate::{
+    config::{Config, StrategyConfig},
+    error::{Error, Result},
+    metrics::{Metric, Metrics},
+};
+use chrono::NaiveDate;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{collections::BTreeMap, io::Write};
+
+/// A parameter space over the strategy, e.g.
+///
+/// ```toml
+/// [sweep]
+/// objective = "xirr"
+///
+/// [sweep.parameters]
+/// amount = [500.0, 1000.0, 2000.0]
+/// interval = { start = 5, end = 40, step = 5 }
+/// ```
+///
+/// Every parameter must be a field of the configured strategy.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SweepConfig {
+    pub objective: Metric,
+    pub parameters: BTreeMap<String, Parameter>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Parameter {
+    List(Vec<Value>),
+    Range {
+        start: Value,
+        end: Value,
+        step: Value,
+    },
+}
+
+impl Parameter {
+    pub fn values(&self) -> Result<Vec<Value>> {
+        match self {
+            Parameter::List(values) => Ok(values.clone()),
+            Parameter::Range { start, end, step } => {
+                match (start.as_u64(), end.as_u64(), step.as_u64()) {
+                    (Some(start), Some(end), Some(step)) if step > 0 => Ok((start..=end)
+                        .step_by(step as usize)
+                        .map(Value::from)
+                        .collect()),
+                    _ => match (start.as_f64(), end.as_f64(), step.as_f64()) {
+                        (Some(start), Some(end), Some(step)) if step > 0. => {
+                            let n = ((end - start) / step + 1e-9).floor();
+                            Ok((0..=n.max(-1.) as i64)
+                                .map(|i| Value::from(start + i as f64 * step))
+                                .collect())
+                        }
+                        _ => Err(Error::Invalid),
+                    },
+                }
+            }
+        }
+    }
+}
+
+pub type Combination = BTreeMap<String, Value>;
+
+/// The cartesian product of all parameter values.
+pub fn combinations(parameters: &BTreeMap<String, Parameter>) -> Result<Vec<Combination>> {
+    let mut combinations = vec![Combination::new()];
+    for (name, parameter) in parameters {
+        let values = parameter.values()?;
+        combinations = combinations
+            .into_iter()
+            .flat_map(|combination| {
+                values.iter().map(move |value| {
+                    let mut combination = combination.clone();
+                    combination.insert(name.clone(), value.clone());
+                    combination
+                })
+            })
+            .collect();
+    }
+    Ok(combinations)
+}
+
+/// The strategy with its fields overridden by `combination`.
+pub fn apply(strategy: &StrategyConfig, combination: &Combination) -> Result<StrategyConfig> {
+    let mut value = serde_json::to_value(strategy)?;
+    let fields = value.as_object_mut().ok_or(Error::Invalid)?;
+    for (name, parameter) in combination {
+        match fields.get_mut(name) {
+            Some(field) if name != "type" => *field = parameter.clone(),
+            _ => return Err(Error::Invalid),
+        }
+    }
+    Ok(serde_json::from_value(value)?)
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Trial {
+    pub parameters: Combination,
+    pub metrics: Metrics,
+}
+
+/// Runs every combination on its own repository in parallel, best first.
+pub fn sweep(
+    config: &Config,
+    sweep: &SweepConfig,
+    history: &[(NaiveDate, f64)],
+) -> Result<Vec<Trial>> {
+    let mut trials = combinations(&sweep.parameters)?
+        .into_par_iter()
+        .map(|parameters| {
+            let mut config = config.clone();
+            config.strategy = apply(&config.strategy, &parameters)?;
+            let repository = config.run(history.to_vec())?;
+            Ok(Trial {
+                parameters,
+                metrics: Metrics::new(&repository),
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    rank(&mut trials, sweep.objective);
+    Ok(trials)
+}
+
+/// Sorts the trials best first, leaving undefined objectives last.
+pub fn rank(trials: &mut [Trial], objective: Metric) {
+    trials.sort_by(|a, b| objective.compare(a.metrics.get(objective), b.metrics.get(objective)));
+}
+
+/// Writes the ranked trials as a tab-separated table.
+pub fn write_table<W: Write>(mut writer: W, trials: &[Trial]) -> std::io::Result<()> {
+    let names: Vec<&String> = trials
+        .first()
+        .map_or(vec![], |trial| trial.parameters.keys().collect());
+    write!(writer, "rank")?;
+    for name in &names {
+        write!(writer, "\t{}", name)?;
+    }
+    writeln!(
+        writer,
+        "\tinvestment\tprofit\ttotal_return\txirr\tmax_drawdown"
+    )?;
+    for (i, trial) in trials.iter().enumerate() {
+        write!(writer, "{}", i + 1)?;
+        for name in &names {
+            write!(writer, "\t{}", trial.parameters[*name])?;
+        }
+        let metrics = &trial.metrics;
+        writeln!(
+            writer,
+            "\t{:.2}\t{:.2}\t{:.4}\t{:.4}\t{:.4}",
+            metrics.investment,
+            metrics.profit,
+            metrics.total_return,
+            metrics.xirr,
+            metrics.max_drawdown
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_combinations() {
+        let mut parameters = BTreeMap::new();
+        parameters.insert(
+            String::from("amount"),
+            Parameter::List(vec![Value::from(500.0), Value::from(1000.0)]),
+        );
+        parameters.insert(
+            String::from("interval"),
+            Parameter::Range {
+                start: Value::from(5),
+                end: Value::from(20),
+                step: Value::from(5),
+            },
+        );
+        let combinations = combinations(&parameters).unwrap();
+        assert_eq!(combinations.len(), 8);
+        assert_eq!(combinations[1]["interval"], Value::from(10));
+        let strategy = apply(
+            &StrategyConfig::Dca {
+                amount: 100.0,
+                interval: 1,
+            },
+            &combinations[7],
+        )
+        .unwrap();
+        assert_eq!(
+            strategy,
+            StrategyConfig::Dca {
+                amount: 1000.0,
+                interval: 20,
+            }
+        );
+        let mut window = Combination::new();
+        window.insert(String::from("window"), Value::from(20));
+        assert!(apply(&strategy, &window).is_err());
+    }
+
+    #[test]
+    fn test_sweep() {
+        let config: Config = toml::from_str(
+            r#"
+            [data]
+            path = "a.csv"
+
+            [strategy]
+            type = "dca"
+            amount = 100.0
+            interval = 1
+
+            [sweep]
+            objective = "profit"
+
+            [sweep.parameters]
+            interval = [2, 1]
+            "#,
+        )
+        .unwrap();
+        let history: Vec<(NaiveDate, f64)> = NaiveDate::from_ymd(2021, 1, 1)
+            .iter_days()
+            .zip(vec![1.0, 1.0, 1.0, 2.0])
+            .collect();
+        let mut sweep_config = config.sweep.clone().unwrap();
+        let trials = sweep(&config, &sweep_config, &history).unwrap();
+        assert_eq!(trials.len(), 2);
+        assert_eq!(trials[0].parameters["interval"], Value::from(1));
+        assert_eq!(trials[0].metrics.investment, 400.0);
+        assert_eq!(trials[0].metrics.profit, 300.0);
+        assert_eq!(trials[1].parameters["interval"], Value::from(2));
+        assert_eq!(trials[1].metrics.investment, 200.0);
+        assert_eq!(trials[1].metrics.profit, 200.0);
+
+        sweep_config.parameters.insert(
+            String::from("window"),
+            Parameter::List(vec![Value::from(20)]),
+        );
+        assert!(
+            if let Err(Error::Invalid) = sweep(&config, &sweep_config, &history) {
+                true
+            } else {
+                false
+            }
+        );
+    }
+}
@@ -0,0 +1,328 @@
+use crate::{
+    config::Config,
+    error::{Error, Result},
+    metrics::{Distribution, Metrics},
+};
+use chrono::NaiveDate;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// How synthetic paths are derived from the daily log returns of a history.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "model", rename_all = "snake_case")]
+pub enum Model {
+    /// Circular block bootstrap of the returns.
+    Bootstrap { block: usize },
+    /// Geometric Brownian motion with the sample drift and volatility.
+    Gbm,
+    /// Two regimes split by the rolling volatility over `window` days, each a
+    /// geometric Brownian motion, switching as a fitted Markov chain.
+    RegimeSwitching { window: usize },
+}
+
+/// Evaluates the strategy over many synthetic paths, fitted on the configured
+/// date range, e.g.
+///
+/// ```toml
+/// [monte_carlo]
+/// model = "bootstrap"
+/// block = 20
+/// paths = 1000
+/// seed = 42
+/// ```
+///
+/// Path `i` is generated from seed `seed + i`, so results do not depend on
+/// the number of threads.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MonteCarloConfig {
+    #[serde(flatten)]
+    pub model: Model,
+    pub paths: usize,
+    #[serde(default)]
+    pub seed: u64,
+}
+
+/// A fitted model that generates paths on the dates of its source history.
+#[derive(Debug, Clone)]
+pub struct Generator {
+    dates: Vec<NaiveDate>,
+    start: f64,
+    returns: Vec<f64>,
+    kind: Kind,
+}
+
+#[derive(Debug, Clone)]
+enum Kind {
+    Bootstrap {
+        block: usize,
+    },
+    Gbm {
+        mu: f64,
+        sigma: f64,
+    },
+    RegimeSwitching {
+        regimes: [(f64, f64); 2],
+        /// The probability of staying in each regime.
+        stay: [f64; 2],
+    },
+}
+
+impl Generator {
+    pub fn new(history: &[(NaiveDate, f64)], model: &Model) -> Result<Self> {
+        if history.len() < 3 {
+            return Err(Error::Insufficient);
+        }
+        let returns: Vec<f64> = history.windows(2).map(|w| (w[1].1 / w[0].1).ln()).collect();
+        let kind = match *model {
+            Model::Bootstrap { block } if block > 0 => Kind::Bootstrap { block },
+            Model::Gbm => {
+                let (mu, sigma) = moments(&returns);
+                Kind::Gbm { mu, sigma }
+            }
+            Model::RegimeSwitching { window } if window > 1 && window < returns.len() => {
+                let volatility: Vec<f64> = returns.windows(window).map(|w| moments(w).1).collect();
+                let mut sorted = volatility.clone();
+                sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+                let median = sorted[sorted.len() / 2];
+                let states: Vec<usize> = volatility
+                    .iter()
+                    .map(|&v| if v > median { 1 } else { 0 })
+                    .collect();
+                let samples = &returns[window - 1..];
+                let regime = |state| {
+                    moments(
+                        &samples
+                            .iter()
+                            .zip(&states)
+                            .filter(|&(_, &s)| s == state)
+                            .map(|(&r, _)| r)
+                            .collect::<Vec<_>>(),
+                    )
+                };
+                let stay = |state| {
+                    let from = states[..states.len() - 1]
+                        .iter()
+                        .filter(|&&s| s == state)
+                        .count();
+                    let kept = states.windows(2).filter(|w| w == &[state, state]).count();
+                    if from == 0 {
+                        1.
+                    } else {
+                        kept as f64 / from as f64
+                    }
+                };
+                Kind::RegimeSwitching {
+                    regimes: [regime(0), regime(1)],
+                    stay: [stay(0), stay(1)],
+                }
+            }
+            _ => return Err(Error::Invalid),
+        };
+        Ok(Self {
+            dates: history.iter().map(|&(date, _)| date).collect(),
+            start: history[0].1,
+            returns,
+            kind,
+        })
+    }
+
+    pub fn generate<R: Rng>(&self, rng: &mut R) -> Vec<(NaiveDate, f64)> {
+        let n = self.returns.len();
+        let returns: Vec<f64> = match self.kind {
+            Kind::Bootstrap { block } => {
+                let mut returns = Vec::with_capacity(n);
+                while returns.len() < n {
+                    let start = rng.gen_range(0..n);
+                    returns.extend((start..start + block).map(|i| self.returns[i % n]));
+                }
+                returns.truncate(n);
+                returns
+            }
+            Kind::Gbm { mu, sigma } => (0..n).map(|_| mu + sigma * normal(rng)).collect(),
+            Kind::RegimeSwitching { regimes, stay } => {
+                let mut state = 0;
+                (0..n)
+                    .map(|_| {
+                        if !rng.gen_bool(stay[state]) {
+                            state = 1 - state;
+                        }
+                        let (mu, sigma) = regimes[state];
+                        mu + sigma * normal(rng)
+                    })
+                    .collect()
+            }
+        };
+        let mut nav = self.start;
+        let mut path = vec![(self.dates[0], nav)];
+        for (&date, r) in self.dates[1..].iter().zip(returns) {
+            nav *= r.exp();
+            path.push((date, nav));
+        }
+        path
+    }
+}
+
+/// Sample mean and standard deviation.
+fn moments(values: &[f64]) -> (f64, f64) {
+    if values.is_empty() {
+        return (0., 0.);
+    }
+    let n = values.len() as f64;
+    let mean = values.iter().sum::<f64>() / n;
+    let variance = values.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / n;
+    (mean, variance.sqrt())
+}
+
+/// A standard normal sample by the Box-Muller transform.
+fn normal<R: Rng>(rng: &mut R) -> f64 {
+    let u: f64 = 1. - rng.gen::<f64>();
+    let v: f64 = rng.gen();
+    (-2. * u.ln()).sqrt() * (2. * std::f64::consts::PI * v).cos()
+}
+
+#[derive(Debug, Serialize)]
+pub struct MonteCarlo {
+    pub paths: usize,
+    pub total_return: Distribution,
+    pub xirr: Distribution,
+    pub max_drawdown: Distribution,
+    pub probability_of_loss: f64,
+    /// The buy-and-hold return of the synthetic fund itself.
+    pub fund_return: Distribution,
+}
+
+impl MonteCarlo {
+    pub fn new(
+        config: &Config,
+        monte_carlo: &MonteCarloConfig,
+        history: &[(NaiveDate, f64)],
+    ) -> Result<Self> {
+        // Only the configured range, with its warm-up, is fitted and simulated.
+        let repository = config.repository(history.to_vec())?;
+        let history: Vec<(NaiveDate, f64)> = repository
+            .warm_up()
+            .iter()
+            .chain(repository.net_asset_value_history())
+            .cloned()
+            .collect();
+        let generator = Generator::new(&history, &monte_carlo.model)?;
+        let runs = (0..monte_carlo.paths as u64)
+            .into_par_iter()
+            .map(|i| {
+                let mut rng = ChaCha8Rng::seed_from_u64(monte_carlo.seed.wrapping_add(i));
+                let repository = config.run(generator.generate(&mut rng))?;
+                let navs = repository.net_asset_value_history();
+                let fund_return = match (navs.first(), navs.last()) {
+                    (Some(&(_, first)), Some(&(_, last))) => last / first - 1.,
+                    _ => f64::NAN,
+                };
+                Ok((Metrics::new(&repository), fund_return))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let distribution = |f: fn(&Metrics) -> f64| {
+            Distribution::new(&runs.iter().map(|(m, _)| f(m)).collect::<Vec<_>>())
+        };
+        Ok(Self {
+            paths: runs.len(),
+            total_return: distribution(|m| m.total_return),
+            xirr: distribution(|m| m.xirr),
+            max_drawdown: distribution(|m| m.max_drawdown),
+            probability_of_loss: runs.iter().filter(|(m, _)| m.profit < 0.).count() as f64
+                / runs.len().max(1) as f64,
+            fund_return: Distribution::new(&runs.iter().map(|&(_, r)| r).collect::<Vec<_>>()),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn history() -> Vec<(NaiveDate, f64)> {
+        NaiveDate::from_ymd(2021, 1, 1)
+            .iter_days()
+            .take(100)
+            .enumerate()
+            .map(|(i, date)| (date, 1.0 + 0.1 * (i as f64 / 5.).sin() + i as f64 / 500.))
+            .collect()
+    }
+
+    #[test]
+    fn test_seeded() {
+        let history = history();
+        for model in &[
+            Model::Bootstrap { block: 5 },
+            Model::Gbm,
+            Model::RegimeSwitching { window: 10 },
+        ] {
+            let generator = Generator::new(&history, model).unwrap();
+            let path = generator.generate(&mut ChaCha8Rng::seed_from_u64(7));
+            assert_eq!(path.len(), history.len());
+            assert_eq!(path[0], history[0]);
+            assert_eq!(path.last().unwrap().0, history.last().unwrap().0);
+            assert!(path.iter().all(|&(_, nav)| nav > 0.));
+            assert_eq!(path, generator.generate(&mut ChaCha8Rng::seed_from_u64(7)));
+            assert_ne!(path, generator.generate(&mut ChaCha8Rng::seed_from_u64(8)));
+        }
+    }
+
+    #[test]
+    fn test_bootstrap_returns() {
+        let history = history();
+        let generator = Generator::new(&history, &Model::Bootstrap { block: 200 }).unwrap();
+        let path = generator.generate(&mut ChaCha8Rng::seed_from_u64(1));
+        let mut returns: Vec<f64> = path.windows(2).map(|w| (w[1].1 / w[0].1).ln()).collect();
+        let mut expected = generator.returns.clone();
+        returns.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        expected.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        for (r, e) in returns.iter().zip(&expected) {
+            assert!((r - e).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn test_range() {
+        // Volatile before the range, then steadily up 1% a day.
+        let history: Vec<(NaiveDate, f64)> = NaiveDate::from_ymd(2021, 1, 1)
+            .iter_days()
+            .take(20)
+            .enumerate()
+            .map(|(i, date)| {
+                let nav = if i < 10 {
+                    [1.0, 2.0][i % 2]
+                } else {
+                    1.01f64.powi(i as i32 - 10)
+                };
+                (date, nav)
+            })
+            .collect();
+        let config: Config = toml::from_str(
+            r#"
+            [data]
+            path = "a.csv"
+            start = "2021-01-11"
+
+            [strategy]
+            type = "dca"
+            amount = 100.0
+            interval = 5
+            "#,
+        )
+        .unwrap();
+        let monte_carlo = MonteCarlo::new(
+            &config,
+            &MonteCarloConfig {
+                model: Model::Gbm,
+                paths: 3,
+                seed: 0,
+            },
+            &history,
+        )
+        .unwrap();
+        let fund_return = 1.01f64.powi(9) - 1.;
+        assert!((monte_carlo.fund_return.min - fund_return).abs() < 1e-9);
+        assert!((monte_carlo.fund_return.max - fund_return).abs() < 1e-9);
+    }
+}
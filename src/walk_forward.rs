@@ -0,0 +1,263 @@
+use crate::{
+    config::Config,
+    error::{Error, Result},
+    metrics::{self, Metrics},
+    sweep::{self, Combination, SweepConfig},
+};
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+/// Rolling windows in trading days, e.g.
+///
+/// ```toml
+/// [walk_forward]
+/// in_sample = 250
+/// out_of_sample = 60
+/// ```
+///
+/// Each in-sample window is optimized over `[sweep]`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WalkForwardConfig {
+    pub in_sample: usize,
+    pub out_of_sample: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Window {
+    pub in_sample: (NaiveDate, NaiveDate),
+    pub out_of_sample: (NaiveDate, NaiveDate),
+    pub parameters: Combination,
+    pub in_sample_metrics: Metrics,
+    pub out_of_sample_metrics: Metrics,
+}
+
+/// How the chosen values of one parameter vary across windows.
+#[derive(Debug, Serialize)]
+pub struct Stability {
+    pub values: Vec<Value>,
+    pub distinct: usize,
+    pub mode: Value,
+    pub mode_share: f64,
+    pub mean: Option<f64>,
+    pub std_dev: Option<f64>,
+}
+
+impl Stability {
+    fn new(values: Vec<Value>) -> Self {
+        let mut counts: Vec<(&Value, usize)> = vec![];
+        for value in &values {
+            match counts.iter_mut().find(|(v, _)| *v == value) {
+                Some((_, count)) => *count += 1,
+                None => counts.push((value, 1)),
+            }
+        }
+        let (mode, count) = counts
+            .iter()
+            .fold((&Value::Null, 0), |best, &(value, count)| {
+                if count > best.1 {
+                    (value, count)
+                } else {
+                    best
+                }
+            });
+        let numbers: Option<Vec<f64>> = values.iter().map(Value::as_f64).collect();
+        let (mean, std_dev) = match numbers {
+            Some(numbers) if !numbers.is_empty() => {
+                let n = numbers.len() as f64;
+                let mean = numbers.iter().sum::<f64>() / n;
+                let variance = numbers.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / n;
+                (Some(mean), Some(variance.sqrt()))
+            }
+            _ => (None, None),
+        };
+        Self {
+            distinct: counts.len(),
+            mode: mode.clone(),
+            mode_share: count as f64 / values.len().max(1) as f64,
+            mean,
+            std_dev,
+            values,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct WalkForward {
+    pub windows: Vec<Window>,
+    /// The out-of-sample equity curves chained together, starting at 1.
+    pub equity_curve: Vec<(NaiveDate, f64)>,
+    pub total_return: f64,
+    pub max_drawdown: f64,
+    pub stability: BTreeMap<String, Stability>,
+}
+
+impl WalkForward {
+    pub fn new(
+        config: &Config,
+        sweep: &SweepConfig,
+        walk_forward: &WalkForwardConfig,
+        history: &[(NaiveDate, f64)],
+    ) -> Result<Self> {
+        let (in_sample, out_of_sample) = (walk_forward.in_sample, walk_forward.out_of_sample);
+        if in_sample == 0 || out_of_sample == 0 {
+            return Err(Error::Invalid);
+        }
+        let dates: Vec<NaiveDate> = config
+            .repository(history.to_vec())?
+            .net_asset_value_history()
+            .iter()
+            .map(|&(date, _)| date)
+            .collect();
+        let mut windows = vec![];
+        let mut equity_curve = vec![];
+        let mut level = 1.;
+        let mut start = 0;
+        while start + in_sample < dates.len() {
+            let end = (start + in_sample + out_of_sample).min(dates.len());
+            let in_sample_range = (dates[start], dates[start + in_sample - 1]);
+            let out_of_sample_range = (dates[start + in_sample], dates[end - 1]);
+            let mut config = config.clone();
+            config.data.start = Some(in_sample_range.0);
+            config.data.end = Some(in_sample_range.1);
+            let best = sweep::sweep(&config, sweep, history)?
+                .into_iter()
+                .next()
+                .ok_or(Error::Invalid)?;
+            config.strategy = sweep::apply(&config.strategy, &best.parameters)?;
+            config.data.start = Some(out_of_sample_range.0);
+            config.data.end = Some(out_of_sample_range.1);
+            let repository = config.run(history.to_vec())?;
+            let capital = metrics::capital(&repository);
+            let curve = metrics::equity_curve(&repository);
+            let base = level;
+            for (date, equity) in curve {
+                level = if capital > 0. {
+                    base * equity / capital
+                } else {
+                    base
+                };
+                equity_curve.push((date, level));
+            }
+            windows.push(Window {
+                in_sample: in_sample_range,
+                out_of_sample: out_of_sample_range,
+                parameters: best.parameters,
+                in_sample_metrics: best.metrics,
+                out_of_sample_metrics: Metrics::new(&repository),
+            });
+            start += out_of_sample;
+        }
+        let mut values: BTreeMap<String, Vec<Value>> = BTreeMap::new();
+        for window in &windows {
+            for (name, value) in &window.parameters {
+                values.entry(name.clone()).or_default().push(value.clone());
+            }
+        }
+        let levels: Vec<f64> = equity_curve.iter().map(|&(_, level)| level).collect();
+        Ok(Self {
+            windows,
+            total_return: level - 1.,
+            max_drawdown: metrics::max_drawdown(&levels),
+            equity_curve,
+            stability: values
+                .into_iter()
+                .map(|(name, values)| (name, Stability::new(values)))
+                .collect(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stability() {
+        let stability = Stability::new(vec![
+            Value::from(10),
+            Value::from(20),
+            Value::from(10),
+            Value::from(20),
+            Value::from(20),
+        ]);
+        assert_eq!(stability.distinct, 2);
+        assert_eq!(stability.mode, Value::from(20));
+        assert_eq!(stability.mode_share, 0.6);
+        assert_eq!(stability.mean, Some(16.0));
+        assert!((stability.std_dev.unwrap() - 24f64.sqrt()).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_windows() {
+        let config: Config = toml::from_str(
+            r#"
+            [data]
+            path = "a.csv"
+
+            [strategy]
+            type = "dca"
+            amount = 100.0
+            interval = 1
+
+            [sweep]
+            objective = "profit"
+
+            [sweep.parameters]
+            interval = [1, 3]
+            "#,
+        )
+        .unwrap();
+        let dates: Vec<NaiveDate> = NaiveDate::from_ymd(2021, 1, 1)
+            .iter_days()
+            .take(10)
+            .collect();
+        let history: Vec<(NaiveDate, f64)> = dates
+            .iter()
+            .cloned()
+            .zip(vec![1.0, 1.0, 1.0, 2.0, 1.0, 1.0, 0.5, 0.5, 1.0, 1.0])
+            .collect();
+        let walk_forward = WalkForward::new(
+            &config,
+            config.sweep.as_ref().unwrap(),
+            &WalkForwardConfig {
+                in_sample: 4,
+                out_of_sample: 3,
+            },
+            &history,
+        )
+        .unwrap();
+        // The third window would have no out-of-sample days.
+        assert_eq!(walk_forward.windows.len(), 2);
+        let (first, second) = (&walk_forward.windows[0], &walk_forward.windows[1]);
+        assert_eq!(first.in_sample, (dates[0], dates[3]));
+        assert_eq!(first.out_of_sample, (dates[4], dates[6]));
+        assert_eq!(second.in_sample, (dates[3], dates[6]));
+        assert_eq!(second.out_of_sample, (dates[7], dates[9]));
+        // Investing daily pays off only while the price rises.
+        assert_eq!(first.parameters["interval"], Value::from(1));
+        assert_eq!(first.in_sample_metrics.profit, 300.0);
+        assert_eq!(first.out_of_sample_metrics.profit, -100.0);
+        assert_eq!(second.parameters["interval"], Value::from(3));
+        assert_eq!(second.in_sample_metrics.profit, -75.0);
+        assert_eq!(second.out_of_sample_metrics.investment, 100.0);
+        assert_eq!(second.out_of_sample_metrics.profit, 100.0);
+        assert_eq!(walk_forward.equity_curve.len(), 6);
+        assert_eq!(walk_forward.equity_curve[0].0, dates[4]);
+
+        assert!(if let Err(Error::Invalid) = WalkForward::new(
+            &config,
+            config.sweep.as_ref().unwrap(),
+            &WalkForwardConfig {
+                in_sample: 0,
+                out_of_sample: 3,
+            },
+            &history,
+        ) {
+            true
+        } else {
+            false
+        });
+    }
+}